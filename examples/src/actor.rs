@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[norpc::service(actor)]
+trait Account {
+    fn deposit(user: u64, n: u64) -> u64;
+    fn balance(user: u64) -> u64;
+    fn hang();
+}
+// No locks are needed because the handlers have exclusive access.
+struct AccountApp {
+    balances: HashMap<u64, u64>,
+}
+#[norpc::async_trait]
+impl Account for AccountApp {
+    async fn deposit(&mut self, user: u64, n: u64) -> u64 {
+        let cur = self.balances.get(&user).copied().unwrap_or(0);
+        // Other requests can't come in while the handler is suspended.
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        let new = cur + n;
+        self.balances.insert(user, new);
+        new
+    }
+    async fn balance(&mut self, user: u64) -> u64 {
+        self.balances.get(&user).copied().unwrap_or(0)
+    }
+    async fn hang(&mut self) {
+        futures::future::pending::<()>().await;
+    }
+}
+
+fn spawn_server() -> norpc::runtime::Channel<AccountRequest, AccountResponse> {
+    use norpc::runtime::*;
+    let app = AccountApp {
+        balances: HashMap::new(),
+    };
+    let (chan, server) = ServerBuilder::new(AccountService::new(app)).build();
+    ::tokio::spawn(server.serve(TokioExecutor));
+    chan
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_actor() {
+    let chan = spawn_server();
+    let mut queries = vec![];
+    for _ in 0..100 {
+        let mut cli = AccountClient::new(chan.clone());
+        queries.push(tokio::spawn(async move { cli.deposit(1, 1).await }));
+    }
+    for q in queries {
+        q.await.unwrap();
+    }
+    // No update is lost.
+    let mut cli = AccountClient::new(chan);
+    assert_eq!(cli.balance(1).await, 100);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_actor_cancel() {
+    let chan = spawn_server();
+    let mut cli = AccountClient::new(chan.clone());
+    let hang = tokio::spawn(async move { cli.hang().await });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    // Cancelling the request puts the app back.
+    hang.abort();
+    let mut cli = AccountClient::new(chan);
+    assert_eq!(cli.deposit(1, 1).await, 1);
+}
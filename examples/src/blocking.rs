@@ -0,0 +1,97 @@
+use std::thread::{self, ThreadId};
+
+#[norpc::service(blocking)]
+trait Hash {
+    fn hash(s: String) -> u64;
+    fn thread() -> ThreadId;
+}
+struct HashApp;
+impl Hash for HashApp {
+    fn hash(&self, s: String) -> u64 {
+        s.len() as u64
+    }
+    fn thread(&self) -> ThreadId {
+        thread::current().id()
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_blocking_tokio() {
+    use norpc::runtime::*;
+    let svc = HashService::with_pool(HashApp, norpc::blocking::Pool::new(TokioExecutor));
+    let (chan, server) = ServerBuilder::new(svc).build();
+    ::tokio::spawn(server.serve(TokioExecutor));
+
+    let mut queries = vec![];
+    for i in 0..10 {
+        let mut cli = HashClient::new(chan.clone());
+        queries.push(::tokio::spawn(async move { cli.hash("a".repeat(i)).await }));
+    }
+    for (i, q) in queries.into_iter().enumerate() {
+        assert_eq!(q.await.unwrap(), i as u64);
+    }
+    // The handler runs on the blocking pool, not on the runtime thread.
+    let mut cli = HashClient::new(chan);
+    assert_ne!(cli.thread().await, thread::current().id());
+}
+
+#[test]
+fn test_blocking_async_std() {
+    async_std::task::block_on(async {
+        use norpc::runtime::*;
+        let svc = HashService::with_pool(HashApp, norpc::blocking::Pool::new(AsyncStdExecutor));
+        let (chan, server) = ServerBuilder::new(svc).build();
+        ::async_std::task::spawn(server.serve(AsyncStdExecutor));
+        let mut cli = HashClient::new(chan);
+        assert_eq!(cli.hash("hello".to_owned()).await, 5);
+        assert_ne!(cli.thread().await, thread::current().id());
+    })
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_blocking_default_pool() {
+    // Without a pool, the handlers run on the default thread pool.
+    let mut cli = HashClient::new(HashService::new(HashApp));
+    assert_eq!(cli.hash("hello".to_owned()).await, 5);
+    assert_ne!(cli.thread().await, thread::current().id());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_blocking_bounded_pool() {
+    use norpc::blocking::{Pool, ThreadPool};
+    use norpc::runtime::*;
+    use std::collections::HashSet;
+
+    let svc = HashService::with_pool(HashApp, Pool::new(ThreadPool::new(2)));
+    let (chan, server) = ServerBuilder::new(svc).build();
+    ::tokio::spawn(server.serve(TokioExecutor));
+    let mut queries = vec![];
+    for _ in 0..10 {
+        let mut cli = HashClient::new(chan.clone());
+        queries.push(::tokio::spawn(async move { cli.thread().await }));
+    }
+    let mut threads = HashSet::new();
+    for q in queries {
+        threads.insert(q.await.unwrap());
+    }
+    assert!(threads.len() <= 2);
+}
+
+struct Unavailable;
+impl norpc::runtime::BlockingSpawn for Unavailable {
+    fn spawn_blocking_obj(
+        &self,
+        _: Box<dyn FnOnce() + Send>,
+    ) -> Result<(), futures::task::SpawnError> {
+        Err(futures::task::SpawnError::shutdown())
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_blocking_spawn_error() {
+    use tower::ServiceExt;
+    // The handler fails instead of panicking.
+    let svc = HashService::with_pool(HashApp, norpc::blocking::Pool::new(Unavailable));
+    let rep = svc.oneshot(HashRequest::thread()).await;
+    assert!(rep.is_err());
+}
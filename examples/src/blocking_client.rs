@@ -0,0 +1,36 @@
+#[norpc::service]
+trait HelloWorld {
+    fn hello(s: String) -> String;
+}
+struct HelloWorldApp;
+#[norpc::async_trait]
+impl HelloWorld for HelloWorldApp {
+    async fn hello(&self, s: String) -> String {
+        format!("Hello, {}", s)
+    }
+}
+
+#[test]
+fn test_blocking_client() {
+    use norpc::runtime::*;
+    let rt = ::tokio::runtime::Runtime::new().unwrap();
+    let (chan, server) = ServerBuilder::new(HelloWorldService::new(HelloWorldApp)).build();
+    rt.spawn(server.serve(TokioExecutor));
+
+    // Plain threads call the service without a runtime.
+    let mut threads = vec![];
+    for i in 0..4 {
+        let mut cli = HelloWorldBlockingClient::new(chan.clone());
+        threads.push(std::thread::spawn(move || cli.hello(i.to_string())));
+    }
+    for (i, th) in threads.into_iter().enumerate() {
+        assert_eq!(th.join().unwrap(), format!("Hello, {}", i));
+    }
+}
+
+#[test]
+fn test_blocking_client_without_channel() {
+    // The blocking client also takes the service itself.
+    let mut cli = HelloWorldBlockingClient::new(HelloWorldService::new(HelloWorldApp));
+    assert_eq!(cli.hello("World".to_owned()), "Hello, World");
+}
@@ -0,0 +1,165 @@
+use norpc::runtime::*;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+#[norpc::service]
+trait IdAlloc {
+    fn alloc(name: u64) -> u64;
+}
+#[norpc::service]
+trait IdStore {
+    fn save(name: u64, id: u64);
+    fn query(name: u64) -> Option<u64>;
+}
+type Events = Arc<Mutex<Vec<String>>>;
+
+struct IdAllocApp {
+    n: Mutex<u64>,
+    id_store_cli: IdStoreClient<Channel<IdStoreRequest, IdStoreResponse>>,
+    events: Events,
+}
+#[norpc::async_trait]
+impl IdAlloc for IdAllocApp {
+    async fn alloc(&self, name: u64) -> u64 {
+        let id = {
+            let mut n = self.n.lock().unwrap();
+            *n += 1;
+            *n
+        };
+        self.id_store_cli.clone().save(name, id).await;
+        id
+    }
+}
+#[norpc::async_trait]
+impl norpc::Lifecycle for IdAllocApp {
+    async fn on_stop(&self) {
+        self.events.lock().unwrap().push("stop id-alloc".to_owned());
+    }
+}
+
+struct IdStoreApp {
+    map: Mutex<HashMap<u64, u64>>,
+    events: Events,
+}
+#[norpc::async_trait]
+impl IdStore for IdStoreApp {
+    async fn save(&self, name: u64, id: u64) {
+        self.map.lock().unwrap().insert(name, id);
+    }
+    async fn query(&self, name: u64) -> Option<u64> {
+        self.map.lock().unwrap().get(&name).cloned()
+    }
+}
+#[norpc::async_trait]
+impl norpc::Lifecycle for IdStoreApp {
+    async fn on_stop(&self) {
+        self.events.lock().unwrap().push("stop id-store".to_owned());
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_container() {
+    let events = Events::default();
+    let mut container = Container::new();
+    // Declared before its dependency.
+    let e = events.clone();
+    container.service("id-alloc", &["id-store"], move |registry| {
+        let chan = registry.get("id-store").unwrap();
+        let app = IdAllocApp {
+            n: Mutex::new(0),
+            id_store_cli: IdStoreClient::new(chan),
+            events: e,
+        };
+        let svc = IdAllocService::new(app);
+        ServerBuilder::new(svc).lifecycle()
+    });
+    let e = events.clone();
+    container.service("id-store", &[], move |_| {
+        let app = IdStoreApp {
+            map: Mutex::new(HashMap::new()),
+            events: e,
+        };
+        let svc = IdStoreService::new(app);
+        ServerBuilder::new(svc).lifecycle()
+    });
+    let running = container.start(TokioExecutor).unwrap();
+    assert_eq!(running.names(), vec!["id-store", "id-alloc"]);
+
+    let chan: Channel<IdAllocRequest, IdAllocResponse> =
+        running.registry().get("id-alloc").unwrap();
+    let mut id_alloc_cli = IdAllocClient::new(chan);
+    let chan: Channel<IdStoreRequest, IdStoreResponse> =
+        running.registry().get("id-store").unwrap();
+    let mut id_store_cli = IdStoreClient::new(chan);
+    let id = id_alloc_cli.alloc(10).await;
+    assert_eq!(id_store_cli.query(10).await, Some(id));
+
+    running.shutdown().await;
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec!["stop id-alloc", "stop id-store"]
+    );
+}
+
+#[test]
+fn test_container_cycle() {
+    let mut container = Container::new();
+    container.service("a", &["b"], |_| {
+        ServerBuilder::new(IdStoreService::new(IdStoreApp {
+            map: Mutex::new(HashMap::new()),
+            events: Events::default(),
+        }))
+    });
+    container.service("b", &["a"], |_| {
+        ServerBuilder::new(IdStoreService::new(IdStoreApp {
+            map: Mutex::new(HashMap::new()),
+            events: Events::default(),
+        }))
+    });
+    match container.start(TokioExecutor) {
+        Err(ContainerError::Cycle(mut names)) => {
+            names.sort();
+            assert_eq!(names, vec!["a", "b"]);
+        }
+        _ => panic!("the cycle is not detected"),
+    }
+}
+
+#[test]
+fn test_container_unknown_dependency() {
+    let mut container = Container::new();
+    container.service("a", &["b"], |_| {
+        ServerBuilder::new(IdStoreService::new(IdStoreApp {
+            map: Mutex::new(HashMap::new()),
+            events: Events::default(),
+        }))
+    });
+    assert!(matches!(
+        container.start(TokioExecutor),
+        Err(ContainerError::UnknownDependency { .. })
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_container_name_taken() {
+    let registry = norpc::Registry::new();
+    registry.register("id-store", 1u64).unwrap();
+    let events = Events::default();
+    let mut container = Container::with_registry(registry.clone());
+    let e = events.clone();
+    container.service("id-store", &[], move |_| {
+        let svc = IdStoreService::new(IdStoreApp {
+            map: Mutex::new(HashMap::new()),
+            events: e,
+        });
+        ServerBuilder::new(svc).lifecycle()
+    });
+    assert!(matches!(
+        container.start(TokioExecutor),
+        Err(ContainerError::Duplicate(_))
+    ));
+    // The server is never spawned and the entry is left as it is.
+    ::tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert!(events.lock().unwrap().is_empty());
+    assert_eq!(registry.get::<u64>("id-store").unwrap(), 1);
+}
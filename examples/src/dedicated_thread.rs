@@ -0,0 +1,49 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[norpc::service(?Send)]
+trait Counter {
+    fn add(n: u64) -> u64;
+    fn thread_name() -> String;
+}
+// Rc<T> is !Send so the app must be made on the server thread.
+struct CounterApp {
+    n: Rc<RefCell<u64>>,
+}
+#[norpc::async_trait(?Send)]
+impl Counter for CounterApp {
+    async fn add(&self, n: u64) -> u64 {
+        *self.n.borrow_mut() += n;
+        *self.n.borrow()
+    }
+    async fn thread_name(&self) -> String {
+        std::thread::current().name().unwrap().to_owned()
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_dedicated_thread() {
+    use norpc::runtime::*;
+    let (chan, handle) = ServerBuilder::spawn_on_thread("counter", || {
+        CounterService::new(CounterApp {
+            n: Rc::new(RefCell::new(0)),
+        })
+    })
+    .unwrap();
+
+    // The channel is Send.
+    fn assert_send<T: Send>(_: &T) {}
+    assert_send(&chan);
+
+    let mut cli = CounterClient::new(chan);
+    cli.add(1).await;
+    cli.add(2).await;
+    assert_eq!(cli.add(3).await, 6);
+    assert_eq!(cli.thread_name().await, "counter");
+    drop(cli);
+
+    // The thread exits after the channels are dropped.
+    tokio::task::spawn_blocking(move || handle.join().unwrap())
+        .await
+        .unwrap();
+}
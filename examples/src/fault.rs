@@ -0,0 +1,88 @@
+use norpc::testing::{FaultLayer, Faults};
+use std::time::{Duration, Instant};
+use tower::{ServiceBuilder, ServiceExt};
+
+#[norpc::service]
+trait Echo {
+    fn echo(n: u64) -> u64;
+    fn flaky(n: u64) -> u64;
+}
+struct EchoApp;
+#[norpc::async_trait]
+impl Echo for EchoApp {
+    async fn echo(&self, n: u64) -> u64 {
+        n
+    }
+    async fn flaky(&self, n: u64) -> u64 {
+        n
+    }
+}
+
+type EchoChannel = norpc::runtime::Channel<EchoRequest, EchoResponse>;
+
+fn spawn_server(faults: FaultLayer) -> EchoChannel {
+    use norpc::runtime::*;
+    let service = ServiceBuilder::new()
+        .layer(faults)
+        .service(EchoService::new(EchoApp));
+    let (chan, server) = ServerBuilder::new(service).build();
+    ::tokio::spawn(server.serve(TokioExecutor));
+    chan
+}
+
+async fn run(seed: u64) -> Vec<bool> {
+    let chan = spawn_server(FaultLayer::new(seed).method("flaky", Faults::new().error(0.5)));
+    let mut out = vec![];
+    for i in 0..100 {
+        // Faults are only injected into flaky.
+        assert!(chan.clone().oneshot(EchoRequest::echo(i)).await.is_ok());
+        out.push(chan.clone().oneshot(EchoRequest::flaky(i)).await.is_ok());
+    }
+    out
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_fault_seed() {
+    let a = run(1).await;
+    assert!(a.contains(&true));
+    assert!(a.contains(&false));
+    // The same seed injects the same faults.
+    assert_eq!(a, run(1).await);
+    assert_ne!(a, run(2).await);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_fault_kinds() {
+    let chan = spawn_server(
+        FaultLayer::new(0)
+            .method(
+                "echo",
+                Faults::new().latency(1.0, Duration::from_millis(100)),
+            )
+            .method("flaky", Faults::new().drop_response(1.0)),
+    );
+    let t = Instant::now();
+    let rep = chan.clone().oneshot(EchoRequest::echo(1)).await;
+    assert!(matches!(rep, Ok(EchoResponse::echo(1))));
+    assert!(t.elapsed() >= Duration::from_millis(100));
+
+    let rep = tokio::time::timeout(
+        Duration::from_millis(100),
+        chan.clone().oneshot(EchoRequest::flaky(1)),
+    )
+    .await;
+    assert!(rep.is_err());
+
+    let chan = spawn_server(FaultLayer::new(0).default_faults(Faults::new().panic(1.0)));
+    assert!(chan.clone().oneshot(EchoRequest::echo(1)).await.is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_fault_configure_clone() {
+    let base = FaultLayer::new(0);
+    // Configuring a clone doesn't panic nor change the original.
+    let chan = spawn_server(base.clone().default_faults(Faults::new().error(1.0)));
+    assert!(chan.clone().oneshot(EchoRequest::echo(1)).await.is_err());
+    let chan = spawn_server(base);
+    assert!(chan.clone().oneshot(EchoRequest::echo(1)).await.is_ok());
+}
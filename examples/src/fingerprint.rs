@@ -0,0 +1,38 @@
+use norpc::Schema;
+
+mod v1 {
+    #[norpc::service]
+    pub trait Counter {
+        fn get(key: String) -> u64;
+        fn add(key: String, n: u64);
+    }
+}
+mod v1_renamed_args {
+    #[norpc::service]
+    pub trait Counter {
+        fn get(name: String) -> u64;
+        fn add(name: String, delta: u64);
+    }
+}
+mod v2 {
+    #[norpc::service]
+    pub trait Counter {
+        fn get(key: String) -> u64;
+        fn add(key: String, n: i64);
+    }
+}
+
+#[test]
+fn test_fingerprint() {
+    assert_eq!(v1::CounterRequest::NAME, "Counter");
+    // Argument names don't matter.
+    assert_eq!(
+        v1::CounterRequest::FINGERPRINT,
+        v1_renamed_args::CounterRequest::FINGERPRINT
+    );
+    // Changing a type does.
+    assert_ne!(
+        v1::CounterRequest::FINGERPRINT,
+        v2::CounterRequest::FINGERPRINT
+    );
+}
@@ -34,3 +34,27 @@ impl HelloWorldLocal for HelloWorldLocalApp {
         format!("Hello, {}", s).into()
     }
 }
+#[tokio::test]
+async fn test_hello_world_local() {
+    let app = HelloWorldLocalApp;
+    let mut cli = HelloWorldLocalClient::new(HelloWorldLocalService::new(app));
+    let rep = cli.hello(Rc::new("World".to_owned())).await;
+    assert_eq!(*rep, "Hello, World");
+}
+
+#[tokio::test]
+async fn test_hello_world_local_server() {
+    use norpc::runtime::*;
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let app = HelloWorldLocalApp;
+            let builder = LocalServerBuilder::new(HelloWorldLocalService::new(app));
+            let (chan, server) = builder.build();
+            ::tokio::task::spawn_local(server.serve(TokioLocalExecutor));
+            let mut cli = HelloWorldLocalClient::new(chan);
+            let rep = cli.hello(Rc::new("World".to_owned())).await;
+            assert_eq!(*rep, "Hello, World");
+        })
+        .await;
+}
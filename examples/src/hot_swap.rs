@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+#[norpc::service]
+trait Greeter {
+    fn greet(name: String) -> String;
+    fn slow_greet(name: String) -> String;
+}
+struct GreeterApp {
+    greeting: &'static str,
+}
+#[norpc::async_trait]
+impl Greeter for GreeterApp {
+    async fn greet(&self, name: String) -> String {
+        format!("{}, {}", self.greeting, name)
+    }
+    async fn slow_greet(&self, name: String) -> String {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        format!("{}, {}", self.greeting, name)
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_hot_swap() {
+    use norpc::runtime::*;
+    let app = GreeterApp { greeting: "Hello" };
+    let (chan, server) = ServerBuilder::new(GreeterService::new(app)).build();
+    let handle = server.handle();
+    ::tokio::spawn(server.serve(TokioExecutor));
+
+    let mut cli = GreeterClient::new(chan.clone());
+    assert_eq!(cli.greet("World".to_owned()).await, "Hello, World");
+
+    let mut slow_cli = GreeterClient::new(chan.clone());
+    let slow = tokio::spawn(async move { slow_cli.slow_greet("World".to_owned()).await });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let new_app = GreeterApp { greeting: "Hi" };
+    assert!(handle.replace(GreeterService::new(new_app)).is_ok());
+
+    // New requests go to the new service through the same channel.
+    assert_eq!(cli.greet("World".to_owned()).await, "Hi, World");
+    // The request in flight finishes on the old one.
+    assert_eq!(slow.await.unwrap(), "Hello, World");
+}
@@ -0,0 +1,128 @@
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+#[norpc::service(json)]
+trait Store {
+    fn read(id: u64) -> Option<String>;
+    fn write(id: u64, s: String, ttl: Option<u64>);
+}
+struct StoreApp {
+    state: RwLock<HashMap<u64, String>>,
+}
+#[norpc::async_trait]
+impl Store for StoreApp {
+    async fn read(&self, id: u64) -> Option<String> {
+        self.state.read().unwrap().get(&id).cloned()
+    }
+    async fn write(&self, id: u64, s: String, _: Option<u64>) {
+        self.state.write().unwrap().insert(id, s);
+    }
+}
+
+async fn post(addr: std::net::SocketAddr, path: &str, body: &str) -> (u16, Value) {
+    post_with_headers(addr, path, "", body).await
+}
+
+async fn post_with_headers(
+    addr: std::net::SocketAddr,
+    path: &str,
+    headers: &str,
+    body: &str,
+) -> (u16, Value) {
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let req = format!(
+        "POST {} HTTP/1.1\r\nHost: localhost\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        headers,
+        body.len(),
+        body
+    );
+    stream.write_all(req.as_bytes()).await.unwrap();
+    let mut rep = String::new();
+    stream.read_to_string(&mut rep).await.unwrap();
+    let status = rep[9..12].parse().unwrap();
+    let body = rep.split("\r\n\r\n").nth(1).unwrap();
+    (status, serde_json::from_str(body).unwrap())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_http_gateway() {
+    use norpc::http::Gateway;
+    use norpc::runtime::*;
+
+    let app = StoreApp {
+        state: RwLock::new(HashMap::new()),
+    };
+    let (chan, server) = ServerBuilder::new(StoreService::new(app)).build();
+    ::tokio::spawn(server.serve(TokioExecutor));
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let gateway = Gateway::new().mount(chan.clone());
+    ::tokio::spawn(gateway.serve(listener));
+
+    assert_eq!(
+        post(addr, "/Store/read", r#"{"id": 1}"#).await,
+        (200, Value::Null)
+    );
+    // The optional trailing argument can be omitted.
+    assert_eq!(
+        post(addr, "/Store/write", r#"{"id": 1, "s": "one"}"#).await,
+        (200, Value::Null)
+    );
+    assert_eq!(
+        post(addr, "/Store/read", r#"{"id": 1}"#).await,
+        (200, json!("one"))
+    );
+    // The service sees the writes through the gateway.
+    let mut cli = StoreClient::new(chan);
+    assert_eq!(cli.read(1).await, Some("one".to_owned()));
+
+    let (status, _) = post(addr, "/Store/delete", r#"{"id": 1}"#).await;
+    assert_eq!(status, 404);
+    let (status, _) = post(addr, "/Unknown/read", r#"{"id": 1}"#).await;
+    assert_eq!(status, 404);
+    let (status, _) = post(addr, "/Store/read", r#"{"id": "one"}"#).await;
+    assert_eq!(status, 400);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_http_gateway_fingerprint() {
+    use norpc::http::Gateway;
+    use norpc::Schema;
+
+    let app = StoreApp {
+        state: RwLock::new(HashMap::new()),
+    };
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    ::tokio::spawn(Gateway::new().mount(StoreService::new(app)).serve(listener));
+
+    let header = format!("norpc-fingerprint: {:016x}\r\n", StoreRequest::FINGERPRINT);
+    let (status, _) = post_with_headers(addr, "/Store/read", &header, r#"{"id": 1}"#).await;
+    assert_eq!(status, 200);
+    let header = format!(
+        "norpc-fingerprint: {:016x}\r\n",
+        StoreRequest::FINGERPRINT ^ 1
+    );
+    let (status, rep) = post_with_headers(addr, "/Store/read", &header, r#"{"id": 1}"#).await;
+    assert_eq!(status, 409);
+    assert!(rep["error"].as_str().unwrap().contains("schema mismatch"));
+    let header = "norpc-fingerprint: xyz\r\n";
+    let (status, _) = post_with_headers(addr, "/Store/read", header, r#"{"id": 1}"#).await;
+    assert_eq!(status, 400);
+}
+
+#[test]
+#[should_panic(expected = "Store is already mounted")]
+fn test_http_gateway_mount_twice() {
+    use norpc::http::Gateway;
+    let new_svc = || {
+        StoreService::new(StoreApp {
+            state: RwLock::new(HashMap::new()),
+        })
+    };
+    let _ = Gateway::new().mount(new_svc()).mount(new_svc());
+}
@@ -0,0 +1,191 @@
+use serde_json::{json, Value};
+
+#[norpc::service(json)]
+trait Calc {
+    fn add(x: i64, y: i64) -> i64;
+    fn neg(x: i64) -> i64;
+}
+struct CalcApp;
+#[norpc::async_trait]
+impl Calc for CalcApp {
+    async fn add(&self, x: i64, y: i64) -> i64 {
+        x + y
+    }
+    async fn neg(&self, x: i64) -> i64 {
+        -x
+    }
+}
+
+#[tokio::test]
+async fn test_jsonrpc() {
+    let input = [
+        r#"{"jsonrpc": "2.0", "method": "add", "params": {"x": 1, "y": 2}, "id": 1}"#,
+        r#"{"jsonrpc": "2.0", "method": "add", "params": [3, 4], "id": 2}"#,
+        // Notification is not answered.
+        r#"{"jsonrpc": "2.0", "method": "neg", "params": [1]}"#,
+        r#"{"jsonrpc": "2.0", "method": "mul", "params": [1, 2], "id": 3}"#,
+        r#"{"jsonrpc": "2.0", "method": "neg", "params": {"x": "one"}, "id": 4}"#,
+        r#"[{"jsonrpc": "2.0", "method": "neg", "params": [5], "id": 5}, {"jsonrpc": "2.0", "method": "neg", "params": [6]}]"#,
+        r#"{"jsonrpc": "2.0", "method""#,
+    ]
+    .join("\n");
+    let reader = futures::io::Cursor::new(input.into_bytes());
+    let mut writer = futures::io::Cursor::new(vec![]);
+    let svc = CalcService::new(CalcApp);
+    norpc::jsonrpc::serve(svc, reader, &mut writer)
+        .await
+        .unwrap();
+
+    let output = String::from_utf8(writer.into_inner()).unwrap();
+    let mut reps: Vec<Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(reps.len(), 6);
+    // Responses can be out of order.
+    reps.sort_by_key(|rep| match rep {
+        Value::Array(_) => 5,
+        rep => rep["id"].as_i64().unwrap_or(100),
+    });
+    assert_eq!(reps[0], json!({"jsonrpc": "2.0", "result": 3, "id": 1}));
+    assert_eq!(reps[1], json!({"jsonrpc": "2.0", "result": 7, "id": 2}));
+    assert_eq!(reps[2]["error"]["code"], -32601);
+    assert_eq!(reps[3]["error"]["code"], -32602);
+    assert_eq!(reps[4], json!([{"jsonrpc": "2.0", "result": -5, "id": 5}]));
+    assert_eq!(reps[5]["error"]["code"], -32700);
+    assert_eq!(reps[5]["id"], Value::Null);
+}
+
+#[tokio::test]
+async fn test_jsonrpc_invalid_request_keeps_id() {
+    let input = r#"{"method": "add", "params": [1, 2], "id": 7}"#;
+    let reader = futures::io::Cursor::new(input.as_bytes().to_vec());
+    let mut writer = futures::io::Cursor::new(vec![]);
+    norpc::jsonrpc::serve(CalcService::new(CalcApp), reader, &mut writer)
+        .await
+        .unwrap();
+    let rep: Value = serde_json::from_slice(&writer.into_inner()).unwrap();
+    assert_eq!(rep["error"]["code"], -32600);
+    assert_eq!(rep["id"], 7);
+}
+
+#[tokio::test]
+async fn test_jsonrpc_handshake() {
+    use norpc::Schema;
+    let fingerprint = format!("{:016x}", CalcRequest::FINGERPRINT);
+    let input = [
+        format!(
+            r#"{{"jsonrpc": "2.0", "method": "rpc.handshake", "params": ["{}"], "id": 1}}"#,
+            fingerprint
+        ),
+        format!(
+            r#"{{"jsonrpc": "2.0", "method": "rpc.handshake", "params": {{"fingerprint": "{:016x}"}}, "id": 2}}"#,
+            CalcRequest::FINGERPRINT ^ 1
+        ),
+    ]
+    .join("\n");
+    let reader = futures::io::Cursor::new(input.into_bytes());
+    let mut writer = futures::io::Cursor::new(vec![]);
+    norpc::jsonrpc::serve(CalcService::new(CalcApp), reader, &mut writer)
+        .await
+        .unwrap();
+    let output = String::from_utf8(writer.into_inner()).unwrap();
+    let mut reps: Vec<Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    reps.sort_by_key(|rep| rep["id"].as_i64());
+    assert_eq!(
+        reps[0]["result"],
+        json!({"service": "Calc", "fingerprint": fingerprint})
+    );
+    assert_eq!(reps[1]["error"]["code"], -32000);
+}
+
+#[tokio::test]
+async fn test_jsonrpc_content_length_over_tokio() {
+    use norpc::jsonrpc::Framing;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio_util::compat::{TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt};
+
+    let (client, server) = tokio::io::duplex(1024);
+    let (reader, writer) = tokio::io::split(server);
+    // Spawning requires the future to be Send.
+    let server = tokio::spawn(norpc::jsonrpc::serve_framed(
+        CalcService::new(CalcApp),
+        Framing::ContentLength,
+        reader.compat(),
+        writer.compat_write(),
+    ));
+
+    let (reader, mut writer) = tokio::io::split(client);
+    let mut reader = BufReader::new(reader);
+    for (i, body) in [
+        r#"{"jsonrpc": "2.0", "method": "add", "params": [1, 2], "id": 1}"#,
+        "{\"jsonrpc\": \"2.0\",\n\"method\": \"neg\", \"params\": [3], \"id\": 2}",
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let msg = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+        writer.write_all(msg.as_bytes()).await.unwrap();
+
+        let mut header = String::new();
+        reader.read_line(&mut header).await.unwrap();
+        let len: usize = header
+            .trim_end()
+            .strip_prefix("Content-Length: ")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let mut blank = String::new();
+        reader.read_line(&mut blank).await.unwrap();
+        assert_eq!(blank, "\r\n");
+        let mut buf = vec![0; len];
+        reader.read_exact(&mut buf).await.unwrap();
+        let rep: Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(rep["id"], i + 1);
+        assert_eq!(rep["result"], [3, -3][i]);
+    }
+    writer.shutdown().await.unwrap();
+    drop(writer);
+    server.await.unwrap().unwrap();
+}
+
+#[norpc::service(json)]
+trait Slow {
+    fn sleep(ms: u64) -> u64;
+}
+struct SlowApp;
+#[norpc::async_trait]
+impl Slow for SlowApp {
+    async fn sleep(&self, ms: u64) -> u64 {
+        tokio::time::sleep(std::time::Duration::from_millis(ms)).await;
+        ms
+    }
+}
+
+#[tokio::test]
+async fn test_jsonrpc_limited_readiness() {
+    use tower::ServiceBuilder;
+    // The second request waits for the first one to finish.
+    let svc = ServiceBuilder::new()
+        .concurrency_limit(1)
+        .service(SlowService::new(SlowApp));
+    let input = [
+        r#"{"jsonrpc": "2.0", "method": "sleep", "params": [50], "id": 1}"#,
+        r#"{"jsonrpc": "2.0", "method": "sleep", "params": [10], "id": 2}"#,
+    ]
+    .join("\n");
+    let reader = futures::io::Cursor::new(input.into_bytes());
+    let mut writer = futures::io::Cursor::new(vec![]);
+    tokio::time::timeout(
+        std::time::Duration::from_secs(5),
+        norpc::jsonrpc::serve(svc, reader, &mut writer),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    let output = String::from_utf8(writer.into_inner()).unwrap();
+    assert_eq!(output.lines().count(), 2);
+}
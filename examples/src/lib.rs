@@ -1,8 +1,32 @@
+#![cfg(test)]
+
+mod actor;
 mod async_std_runtime;
+mod blocking;
+mod blocking_client;
 mod client_drop;
 mod concurrency;
+mod container;
+mod dedicated_thread;
+mod fault;
+mod fingerprint;
 mod hello_world;
+mod hot_swap;
+mod http_gateway;
+mod jsonrpc;
 mod kvstore;
+mod lifecycle;
+mod location_transparency;
+mod method_id;
+mod mock;
 mod no_runtime;
 mod panic;
 mod rate_limit;
+mod record;
+mod registry;
+mod replicas;
+mod router;
+mod sharding;
+mod sim;
+mod spawn_test;
+mod supervisor;
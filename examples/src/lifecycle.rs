@@ -0,0 +1,119 @@
+use std::sync::{Arc, Mutex};
+
+#[norpc::service]
+trait Log {
+    fn write(s: String);
+    fn write_slow(s: String);
+}
+#[derive(Default)]
+struct LogApp {
+    buf: Mutex<Vec<String>>,
+    // Written on stop.
+    disk: Arc<Mutex<Vec<String>>>,
+}
+#[norpc::async_trait]
+impl Log for LogApp {
+    async fn write(&self, s: String) {
+        self.buf.lock().unwrap().push(s);
+    }
+    async fn write_slow(&self, s: String) {
+        ::tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        self.buf.lock().unwrap().push(s);
+    }
+}
+#[norpc::async_trait]
+impl norpc::Lifecycle for LogApp {
+    async fn on_start(&self) {
+        self.buf.lock().unwrap().push("start".to_owned());
+    }
+    async fn on_stop(&self) {
+        // Flush the buffer.
+        let mut buf = self.buf.lock().unwrap();
+        self.disk.lock().unwrap().append(&mut buf);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_lifecycle_drop() {
+    use norpc::runtime::*;
+    let app = LogApp::default();
+    let disk = app.disk.clone();
+    let svc = LogService::new(app);
+    let (chan, server) = ServerBuilder::new(svc).lifecycle().build();
+    let server = ::tokio::spawn(server.serve(TokioExecutor));
+
+    let mut cli = LogClient::new(chan);
+    cli.write("a".to_owned()).await;
+    cli.write("b".to_owned()).await;
+    // The server stops after the last channel is dropped.
+    drop(cli);
+    server.await.unwrap();
+    assert_eq!(*disk.lock().unwrap(), vec!["start", "a", "b"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_lifecycle_shutdown() {
+    use norpc::runtime::*;
+    let app = LogApp::default();
+    let disk = app.disk.clone();
+    let svc = LogService::new(app);
+    let (chan, server) = ServerBuilder::new(svc).lifecycle().build();
+    let handle = server.handle();
+    let server = ::tokio::spawn(server.serve(TokioExecutor));
+
+    let mut cli = LogClient::new(chan.clone());
+    cli.write("a".to_owned()).await;
+    handle.shutdown();
+    server.await.unwrap();
+    assert_eq!(*disk.lock().unwrap(), vec!["start", "a"]);
+
+    // The requests after shutdown fail.
+    use tower::ServiceExt;
+    assert!(chan
+        .oneshot(LogRequest::write("b".to_owned()))
+        .await
+        .is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_lifecycle_waits_handlers() {
+    use norpc::runtime::*;
+    let app = LogApp::default();
+    let disk = app.disk.clone();
+    let svc = LogService::new(app);
+    let (chan, server) = ServerBuilder::new(svc).lifecycle().build();
+    let handle = server.handle();
+    let server = ::tokio::spawn(server.serve(TokioExecutor));
+
+    let mut cli = LogClient::new(chan);
+    let write = ::tokio::spawn(async move { cli.write_slow("a".to_owned()).await });
+    ::tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    handle.shutdown();
+    server.await.unwrap();
+    // on_stop is called after the handler in flight finishes.
+    assert_eq!(*disk.lock().unwrap(), vec!["start", "a"]);
+    write.await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_lifecycle_replaced() {
+    use norpc::runtime::*;
+    let old_app = LogApp::default();
+    let old_disk = old_app.disk.clone();
+    let new_app = LogApp::default();
+    let new_disk = new_app.disk.clone();
+    let (chan, server) = ServerBuilder::new(LogService::new(old_app))
+        .lifecycle()
+        .build();
+    let handle = server.handle();
+    let server = ::tokio::spawn(server.serve(TokioExecutor));
+
+    handle.replace(LogService::new(new_app)).ok();
+    let mut cli = LogClient::new(chan);
+    cli.write("a".to_owned()).await;
+    drop(cli);
+    server.await.unwrap();
+    // on_stop is called on the service the server has at the end.
+    assert!(old_disk.lock().unwrap().is_empty());
+    assert_eq!(*new_disk.lock().unwrap(), vec!["a"]);
+}
@@ -0,0 +1,48 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[norpc::service(client_impl)]
+trait Counter {
+    fn incr(n: u64) -> u64;
+}
+struct CounterApp {
+    n: AtomicU64,
+}
+#[norpc::async_trait]
+impl Counter for CounterApp {
+    async fn incr(&self, n: u64) -> u64 {
+        self.n.fetch_add(n, Ordering::SeqCst) + n
+    }
+}
+struct CounterStub;
+#[norpc::async_trait]
+impl Counter for CounterStub {
+    async fn incr(&self, _: u64) -> u64 {
+        42
+    }
+}
+
+// This function doesn't know if the counter is local or behind a channel.
+async fn incr_twice(counter: &dyn Counter) -> u64 {
+    counter.incr(1).await;
+    counter.incr(1).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_location_transparency() {
+    use norpc::runtime::*;
+
+    let app = CounterApp {
+        n: AtomicU64::new(0),
+    };
+    assert_eq!(incr_twice(&app).await, 2);
+
+    let app = CounterApp {
+        n: AtomicU64::new(0),
+    };
+    let (chan, server) = ServerBuilder::new(CounterService::new(app)).build();
+    ::tokio::spawn(server.serve(TokioExecutor));
+    let cli: Box<dyn Counter> = Box::new(CounterClient::new(chan));
+    assert_eq!(incr_twice(cli.as_ref()).await, 2);
+
+    assert_eq!(incr_twice(&CounterStub).await, 42);
+}
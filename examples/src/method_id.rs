@@ -0,0 +1,50 @@
+use norpc::Schema;
+
+#[norpc::service]
+trait Account {
+    #[id = 1]
+    fn balance(user: u64) -> u64;
+    #[id = 3]
+    fn deposit(user: u64, amount: u64);
+    #[id = 2]
+    #[deprecated(note = "use deposit")]
+    fn add(user: u64, amount: u64);
+}
+struct AccountApp;
+#[norpc::async_trait]
+impl Account for AccountApp {
+    async fn balance(&self, _: u64) -> u64 {
+        100
+    }
+    async fn deposit(&self, _: u64, _: u64) {}
+    async fn add(&self, _: u64, _: u64) {}
+}
+
+#[tokio::test]
+async fn test_method_id() {
+    assert_eq!(
+        AccountRequest::METHODS,
+        &[(1, "balance"), (3, "deposit"), (2, "add")]
+    );
+    let req = AccountRequest::deposit(1, 10);
+    assert_eq!(req.method_id(), 3);
+    assert_eq!(req.method_name(), "deposit");
+
+    assert_eq!(AccountRequest::lookup_method(2).unwrap(), "add");
+    let e = AccountRequest::lookup_method(4).unwrap_err();
+    assert_eq!(e.to_string(), "unknown method Account/#4");
+
+    let mut cli = AccountClient::new(AccountService::new(AccountApp));
+    assert_eq!(cli.balance(1).await, 100);
+    #[allow(deprecated)]
+    cli.add(1, 10).await;
+}
+
+#[norpc::service(mock)]
+trait Empty {}
+
+#[test]
+fn test_method_id_empty_service() {
+    assert!(EmptyRequest::METHODS.is_empty());
+    assert!(EmptyRequest::lookup_method(0).is_err());
+}
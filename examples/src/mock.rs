@@ -0,0 +1,51 @@
+#[norpc::service(mock, client_impl)]
+trait IdStore {
+    fn save(name: u64, id: u64);
+    fn query(name: u64) -> Option<u64>;
+    fn len() -> usize;
+}
+
+// The code under test only sees a client.
+async fn copy_id(cli: &impl IdStore, from: u64, to: u64) -> bool {
+    match cli.query(from).await {
+        Some(id) => {
+            cli.save(to, id).await;
+            true
+        }
+        None => false,
+    }
+}
+
+#[tokio::test]
+async fn test_mock() {
+    let mock = IdStoreMock::new();
+    mock.expect_query()
+        .with(|(name,)| *name == 1)
+        .return_const(Some(10));
+    mock.expect_query().returning(|_| None);
+    let save = mock
+        .expect_save()
+        .with(|(name, id)| *name == 2 && *id == 10)
+        .returning(|_| ())
+        .times(1);
+    let cli = IdStoreClient::new(mock.clone());
+
+    assert!(copy_id(&cli, 1, 2).await);
+    assert!(!copy_id(&cli, 3, 4).await);
+    assert_eq!(save.call_count(), 1);
+    mock.checkpoint();
+}
+
+#[tokio::test]
+#[should_panic(expected = "unexpected call to IdStore::len")]
+async fn test_mock_unexpected_call() {
+    let cli = IdStoreClient::new(IdStoreMock::new());
+    cli.len().await;
+}
+
+#[test]
+#[should_panic(expected = "IdStore::save is expected to be called 1 times but called 0 times")]
+fn test_mock_unsatisfied() {
+    let mock = IdStoreMock::new();
+    mock.expect_save().returning(|_| ()).times(1);
+}
@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use tower::ServiceBuilder;
+
+#[norpc::service(serde)]
+trait KVStore {
+    fn read(id: u64) -> Option<String>;
+    fn write(id: u64, s: String);
+}
+struct KVStoreApp {
+    state: RwLock<HashMap<u64, String>>,
+}
+#[norpc::async_trait]
+impl KVStore for KVStoreApp {
+    async fn read(&self, id: u64) -> Option<String> {
+        self.state.read().unwrap().get(&id).cloned()
+    }
+    async fn write(&self, id: u64, s: String) {
+        self.state.write().unwrap().insert(id, s);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_record_replay() {
+    use norpc::record::*;
+    use norpc::runtime::*;
+
+    let path = std::env::temp_dir().join(format!("norpc-record-{}.jsonl", std::process::id()));
+
+    let app = KVStoreApp {
+        state: RwLock::new(HashMap::new()),
+    };
+    let (chan, server) = ServerBuilder::new(KVStoreService::new(app)).build();
+    ::tokio::spawn(server.serve(TokioExecutor));
+    let chan = ServiceBuilder::new()
+        .layer(RecordLayer::create(&path).unwrap())
+        .service(chan);
+    let mut cli = KVStoreClient::new(chan);
+    assert_eq!(cli.read(1).await, None);
+    cli.write(1, "one".to_owned()).await;
+    assert_eq!(cli.read(1).await, Some("one".to_owned()));
+    drop(cli);
+
+    let recording = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(recording.lines().count(), 3);
+
+    // The same requests get the same responses without the app.
+    let replay = Replay::open(&path).unwrap();
+    let (chan, server) = ServerBuilder::new(replay).build();
+    ::tokio::spawn(server.serve(TokioExecutor));
+    let mut cli = KVStoreClient::new(chan);
+    assert_eq!(cli.read(1).await, None);
+    cli.write(1, "one".to_owned()).await;
+    assert_eq!(cli.read(1).await, Some("one".to_owned()));
+
+    std::fs::remove_file(&path).ok();
+}
+
+#[tokio::test]
+async fn test_replay_miss() {
+    use norpc::record::*;
+    use tower::ServiceExt;
+
+    let entry = Entry {
+        time: 0,
+        elapsed: 0,
+        request: KVStoreRequest::read(1),
+        response: KVStoreResponse::read(Some("one".to_owned())),
+    };
+    let recording = serde_json::to_string(&entry).unwrap();
+    let replay = Replay::<KVStoreRequest, KVStoreResponse>::new(recording.as_bytes()).unwrap();
+    let rep = replay.clone().oneshot(KVStoreRequest::read(1)).await;
+    assert!(matches!(rep, Ok(KVStoreResponse::read(Some(_)))));
+    // The only recorded response is used up.
+    let e = replay.oneshot(KVStoreRequest::read(1)).await.err().unwrap();
+    assert_eq!(e.to_string(), r#"no recorded response for {"read":1}"#);
+}
+
+struct SharedBuf(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+impl std::io::Write for SharedBuf {
+    fn write(&mut self, x: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(x)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+struct BrokenWriter;
+impl std::io::Write for BrokenWriter {
+    fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+        Err(std::io::ErrorKind::BrokenPipe.into())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_record_errors() {
+    use norpc::record::*;
+    use std::sync::{Arc, Mutex};
+    use tower::ServiceExt;
+
+    let errors = Arc::new(Mutex::new(vec![]));
+    let on_error = {
+        let errors = errors.clone();
+        move |e: RecordError| errors.lock().unwrap().push(e)
+    };
+    let buf = Arc::new(Mutex::new(vec![]));
+
+    // A map with non-string keys can't be serialized in JSON.
+    // Such a request is not recorded but the call succeeds.
+    let svc = ServiceBuilder::new()
+        .layer(RecordLayer::new(SharedBuf(buf.clone())).on_error(on_error.clone()))
+        .service(tower::service_fn(
+            |m: HashMap<(u64, u64), u64>| async move { Ok::<_, ()>(m.len()) },
+        ));
+    let req = HashMap::from([((1, 2), 3)]);
+    assert_eq!(svc.oneshot(req).await, Ok(1));
+    assert!(buf.lock().unwrap().is_empty());
+
+    let svc = ServiceBuilder::new()
+        .layer(RecordLayer::new(BrokenWriter).on_error(on_error))
+        .service(tower::service_fn(|x: u64| async move { Ok::<_, ()>(x) }));
+    assert_eq!(svc.oneshot(2).await, Ok(2));
+
+    let errors = errors.lock().unwrap();
+    assert_eq!(errors.len(), 2);
+    assert!(matches!(errors[0], RecordError::Serialize(_)));
+    assert!(matches!(errors[1], RecordError::Write(_)));
+}
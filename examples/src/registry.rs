@@ -0,0 +1,131 @@
+use norpc::runtime::*;
+use norpc::{Registry, RegistryError};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::RwLock;
+
+#[norpc::service]
+trait IdAlloc {
+    fn alloc(name: u64) -> u64;
+}
+#[norpc::service]
+trait IdStore {
+    fn save(name: u64, id: u64);
+    fn query(name: u64) -> Option<u64>;
+}
+type IdAllocClientT = IdAllocClient<Channel<IdAllocRequest, IdAllocResponse>>;
+type IdStoreClientT = IdStoreClient<Channel<IdStoreRequest, IdStoreResponse>>;
+
+struct IdAllocApp {
+    n: AtomicU64,
+    registry: Registry,
+}
+#[norpc::async_trait]
+impl IdAlloc for IdAllocApp {
+    async fn alloc(&self, name: u64) -> u64 {
+        let id = self.n.fetch_add(1, Ordering::SeqCst);
+        // The store is looked up late so it can be registered after this service.
+        let mut id_store_cli: IdStoreClientT = self.registry.wait("id-store").await.unwrap();
+        id_store_cli.save(name, id).await;
+        id
+    }
+}
+
+struct IdStoreApp {
+    map: RwLock<HashMap<u64, u64>>,
+    registry: Registry,
+}
+#[norpc::async_trait]
+impl IdStore for IdStoreApp {
+    async fn save(&self, name: u64, id: u64) {
+        self.map.write().await.insert(name, id);
+    }
+    async fn query(&self, name: u64) -> Option<u64> {
+        if let Some(id) = self.map.read().await.get(&name).cloned() {
+            return Some(id);
+        }
+        // Allocate on a miss, which calls back this service.
+        let mut id_alloc_cli: IdAllocClientT = self.registry.get("id-alloc").unwrap();
+        Some(id_alloc_cli.alloc(name).await)
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_registry() {
+    let registry = Registry::new();
+
+    let app = IdAllocApp {
+        n: AtomicU64::new(100),
+        registry: registry.clone(),
+    };
+    let (chan, server) = ServerBuilder::new(IdAllocService::new(app)).build();
+    ::tokio::spawn(server.serve(TokioExecutor));
+    registry
+        .register("id-alloc", IdAllocClient::new(chan))
+        .unwrap();
+
+    // The store isn't registered yet so the call waits.
+    let mut id_alloc_cli: IdAllocClientT = registry.get("id-alloc").unwrap();
+    let pending = ::tokio::spawn(async move { id_alloc_cli.alloc(1).await });
+
+    let app = IdStoreApp {
+        map: RwLock::new(HashMap::new()),
+        registry: registry.clone(),
+    };
+    let (chan, server) = ServerBuilder::new(IdStoreService::new(app)).build();
+    ::tokio::spawn(server.serve(TokioExecutor));
+    registry
+        .register("id-store", IdStoreClient::new(chan))
+        .unwrap();
+    assert_eq!(pending.await.unwrap(), 100);
+
+    let mut id_store_cli: IdStoreClientT = registry.get("id-store").unwrap();
+    assert_eq!(id_store_cli.query(1).await, Some(100));
+    assert_eq!(id_store_cli.query(2).await, Some(101));
+    assert_eq!(id_store_cli.query(2).await, Some(101));
+}
+
+#[test]
+fn test_registry_errors() {
+    let registry = Registry::new();
+    assert!(matches!(
+        registry.get::<u64>("x"),
+        Err(RegistryError::NotFound(_))
+    ));
+    registry.register("x", 1u64).unwrap();
+    assert!(matches!(
+        registry.register("x", 2u64),
+        Err(RegistryError::AlreadyRegistered(_))
+    ));
+    assert!(matches!(
+        registry.get::<String>("x"),
+        Err(RegistryError::TypeMismatch(_))
+    ));
+    assert_eq!(registry.get::<u64>("x").unwrap(), 1);
+    assert!(registry.unregister("x"));
+    assert!(!registry.unregister("x"));
+}
+
+#[tokio::test]
+async fn test_registry_wait_given_up() {
+    let registry = Registry::new();
+    // The waits which time out are dropped from the registry.
+    for _ in 0..10 {
+        let wait = registry.wait::<u64>("x");
+        assert!(
+            ::tokio::time::timeout(std::time::Duration::from_millis(1), wait)
+                .await
+                .is_err()
+        );
+        // Only the last one is left until the next wait.
+        assert_eq!(registry.waiters("x"), 1);
+    }
+    let wait = ::tokio::spawn({
+        let registry = registry.clone();
+        async move { registry.wait::<u64>("x").await }
+    });
+    ::tokio::task::yield_now().await;
+    assert_eq!(registry.waiters("x"), 1);
+    registry.register("x", 1u64).unwrap();
+    assert_eq!(wait.await.unwrap().unwrap(), 1);
+}
@@ -0,0 +1,79 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+#[norpc::service]
+trait Worker {
+    fn work(x: u64) -> (u64, usize);
+}
+struct WorkerApp {
+    replica: usize,
+}
+#[norpc::async_trait]
+impl Worker for WorkerApp {
+    async fn work(&self, x: u64) -> (u64, usize) {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        (x * 2, self.replica)
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_replicas() {
+    use norpc::runtime::*;
+    let mut replica = 0;
+    let (chan, servers) = ServerBuilder::replicas(4, || {
+        let app = WorkerApp { replica };
+        replica += 1;
+        WorkerService::new(app)
+    });
+    let mut handles = vec![];
+    for server in servers {
+        handles.push(::tokio::spawn(server.serve(TokioExecutor)));
+    }
+
+    let cli = WorkerClient::new(chan);
+    let mut queue = futures::stream::FuturesUnordered::new();
+    for i in 0..100 {
+        let mut cli = cli.clone();
+        queue.push(async move { (i, cli.work(i).await) });
+    }
+    use futures::StreamExt;
+    let mut used = HashSet::new();
+    while let Some((i, (y, replica))) = queue.next().await {
+        assert_eq!(y, i * 2);
+        used.insert(replica);
+    }
+    // The requests in flight are spread over the replicas.
+    assert_eq!(used.len(), 4);
+
+    // All the servers stop when the channel is dropped.
+    drop(cli);
+    for handle in handles {
+        tokio::time::timeout(Duration::from_secs(1), handle)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_replicas_stream_abort() {
+    use norpc::runtime::*;
+    use tower::Service;
+    let mut replica = 0;
+    let (mut chan, servers) = ServerBuilder::replicas(4, || {
+        let app = WorkerApp { replica };
+        replica += 1;
+        WorkerService::new(app)
+    });
+    for server in servers {
+        ::tokio::spawn(server.serve(TokioExecutor));
+    }
+
+    // A request aborts the previous one of the stream as on a single server
+    // because it goes to the same replica.
+    let first = ::tokio::spawn(chan.call(WorkerRequest::work(1)));
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    let second = chan.call(WorkerRequest::work(2)).await;
+    assert!(first.await.unwrap().is_err());
+    assert!(matches!(second, Ok(WorkerResponse::work((4, _)))));
+}
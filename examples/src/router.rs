@@ -0,0 +1,183 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+#[norpc::service]
+trait Greeter {
+    fn greet(name: String) -> String;
+    fn slow_greet(name: String) -> String;
+}
+struct GreeterApp;
+#[norpc::async_trait]
+impl Greeter for GreeterApp {
+    async fn greet(&self, name: String) -> String {
+        format!("Hello, {}", name)
+    }
+    async fn slow_greet(&self, name: String) -> String {
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        format!("Hello, {}", name)
+    }
+}
+
+#[norpc::service]
+trait Adder {
+    fn add(x: u64, y: u64) -> u64;
+}
+struct AdderApp {
+    scale: u64,
+}
+#[norpc::async_trait]
+impl Adder for AdderApp {
+    async fn add(&self, x: u64, y: u64) -> u64 {
+        self.scale * (x + y)
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_router() {
+    use norpc::runtime::*;
+    let mut router = Router::new();
+    let greeter_chan = router.add(GreeterService::new(GreeterApp));
+    let adder_chan = router.add(AdderService::new(AdderApp { scale: 1 }));
+    let server = ::tokio::spawn(router.serve(TokioExecutor));
+
+    let mut greeter_cli = GreeterClient::new(greeter_chan);
+    let mut adder_cli = AdderClient::new(adder_chan.clone());
+    assert_eq!(greeter_cli.greet("World".to_owned()).await, "Hello, World");
+    assert_eq!(adder_cli.add(1, 2).await, 3);
+
+    // Dropping a client cancels only its own request.
+    let mut slow_cli = greeter_cli.clone();
+    let slow = tokio::spawn(async move { slow_cli.slow_greet("World".to_owned()).await });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    slow.abort();
+    assert_eq!(adder_cli.add(3, 4).await, 7);
+
+    // The server stops when all the channels of all the services are dropped.
+    drop(greeter_cli);
+    drop(adder_cli);
+    drop(adder_chan);
+    tokio::time::timeout(Duration::from_secs(1), server)
+        .await
+        .unwrap()
+        .unwrap();
+}
+
+/// Counts the requests to the services it wraps.
+#[derive(Clone)]
+struct CountLayer(Arc<AtomicUsize>);
+impl<S> tower::Layer<S> for CountLayer {
+    type Service = Count<S>;
+    fn layer(&self, inner: S) -> Count<S> {
+        Count(inner, self.0.clone())
+    }
+}
+struct Count<S>(S, Arc<AtomicUsize>);
+impl<X, S: tower::Service<X>> tower::Service<X> for Count<S> {
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+    fn poll_ready(&mut self, ctx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.0.poll_ready(ctx)
+    }
+    fn call(&mut self, req: X) -> S::Future {
+        self.1.fetch_add(1, Ordering::SeqCst);
+        self.0.call(req)
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_router_layer() {
+    use norpc::runtime::*;
+    let n = Arc::new(AtomicUsize::new(0));
+    // The layer applies to all the services.
+    let mut router = Router::with_layer(CountLayer(n.clone()));
+    let greeter_chan = router.add(GreeterService::new(GreeterApp));
+    let (adder_chan, adder_handle) = router
+        .route(AdderService::new(AdderApp { scale: 1 }))
+        .build();
+    ::tokio::spawn(router.serve(TokioExecutor));
+
+    let mut greeter_cli = GreeterClient::new(greeter_chan);
+    assert_eq!(greeter_cli.greet("World".to_owned()).await, "Hello, World");
+    let mut adder_cli = AdderClient::new(adder_chan);
+    assert_eq!(adder_cli.add(1, 2).await, 3);
+    assert_eq!(n.load(Ordering::SeqCst), 2);
+
+    // and to the replaced ones.
+    adder_handle
+        .replace(AdderService::new(AdderApp { scale: 2 }))
+        .ok()
+        .unwrap();
+    assert_eq!(adder_cli.add(1, 2).await, 6);
+    assert_eq!(n.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_router_replace_and_shutdown() {
+    use norpc::runtime::*;
+    use tower::ServiceExt;
+    let mut router = Router::new();
+    let greeter_chan = router.add(GreeterService::new(GreeterApp));
+    let (adder_chan, adder_handle) = router
+        .route(AdderService::new(AdderApp { scale: 1 }))
+        .build();
+    let handle = router.handle();
+    let server = ::tokio::spawn(router.serve(TokioExecutor));
+
+    let mut cli = AdderClient::new(adder_chan.clone());
+    assert_eq!(cli.add(1, 2).await, 3);
+    // Only the service of the handle is replaced.
+    adder_handle
+        .replace(AdderService::new(AdderApp { scale: 2 }))
+        .ok()
+        .unwrap();
+    assert_eq!(cli.add(1, 2).await, 6);
+    let mut greeter_cli = GreeterClient::new(greeter_chan.clone());
+    assert_eq!(greeter_cli.greet("World".to_owned()).await, "Hello, World");
+
+    handle.shutdown();
+    server.await.unwrap();
+    assert!(adder_chan.oneshot(AdderRequest::add(1, 2)).await.is_err());
+    assert!(greeter_chan
+        .oneshot(GreeterRequest::greet("World".to_owned()))
+        .await
+        .is_err());
+}
+
+#[derive(Clone, Default)]
+struct EventsApp {
+    events: Arc<std::sync::Mutex<Vec<&'static str>>>,
+}
+#[norpc::async_trait]
+impl Adder for EventsApp {
+    async fn add(&self, x: u64, y: u64) -> u64 {
+        x + y
+    }
+}
+#[norpc::async_trait]
+impl norpc::Lifecycle for EventsApp {
+    async fn on_start(&self) {
+        self.events.lock().unwrap().push("start");
+    }
+    async fn on_stop(&self) {
+        self.events.lock().unwrap().push("stop");
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_router_lifecycle() {
+    use norpc::runtime::*;
+    let app = EventsApp::default();
+    let events = app.events.clone();
+    let mut router = Router::new();
+    let (chan, _) = router.route(AdderService::new(app)).lifecycle().build();
+    let server = ::tokio::spawn(router.serve(TokioExecutor));
+
+    let mut cli = AdderClient::new(chan);
+    assert_eq!(cli.add(1, 2).await, 3);
+    drop(cli);
+    server.await.unwrap();
+    assert_eq!(*events.lock().unwrap(), vec!["start", "stop"]);
+}
@@ -0,0 +1,213 @@
+use norpc::runtime::*;
+use norpc::shard::BroadcastError;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+#[norpc::service]
+trait KVStore {
+    fn read(#[shard_key] id: u64) -> Option<String>;
+    fn write(#[shard_key] id: u64, s: String);
+    #[broadcast]
+    fn list() -> Vec<(u64, String)>;
+}
+struct KVStoreApp {
+    state: RwLock<HashMap<u64, String>>,
+}
+#[norpc::async_trait]
+impl KVStore for KVStoreApp {
+    async fn read(&self, id: u64) -> Option<String> {
+        self.state.read().await.get(&id).cloned()
+    }
+    async fn write(&self, id: u64, s: String) {
+        self.state.write().await.insert(id, s);
+    }
+    async fn list(&self) -> Vec<(u64, String)> {
+        let mut out: Vec<_> = self
+            .state
+            .read()
+            .await
+            .iter()
+            .map(|(k, v)| (*k, v.clone()))
+            .collect();
+        out.sort();
+        out
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_sharding() {
+    const N: usize = 4;
+    let mut chans = vec![];
+    for _ in 0..N {
+        let app = KVStoreApp {
+            state: RwLock::new(HashMap::new()),
+        };
+        let (chan, server) = ServerBuilder::new(KVStoreService::new(app)).build();
+        ::tokio::spawn(server.serve(TokioExecutor));
+        chans.push(chan);
+    }
+    let mut cli = KVStoreShardedClient::new(chans);
+    assert_eq!(cli.partitions(), N);
+
+    for id in 0..100 {
+        cli.write(id, id.to_string()).await;
+    }
+    for id in 0..100 {
+        assert_eq!(cli.read(id).await, Some(id.to_string()));
+    }
+
+    // Each key is stored only in the partition it hashes to.
+    let mut total = 0;
+    for i in 0..N {
+        let kvs = cli.partition(i).list().await;
+        for (id, _) in &kvs {
+            let req = KVStoreRequest::read(*id);
+            assert_eq!(req.partition(N), Some(i));
+        }
+        assert!(!kvs.is_empty());
+        total += kvs.len();
+    }
+    assert_eq!(total, 100);
+    assert_eq!(KVStoreRequest::list().partition(N), None);
+}
+
+#[test]
+#[should_panic(expected = "the number of partitions must be positive")]
+fn test_partition_zero() {
+    KVStoreRequest::read(1).partition(0);
+}
+
+#[norpc::service]
+trait Counter {
+    fn incr(#[shard_key] key: String) -> u64;
+    #[broadcast]
+    fn total(wait: Duration) -> u64;
+}
+struct CounterApp {
+    counts: Mutex<HashMap<String, u64>>,
+}
+#[norpc::async_trait]
+impl Counter for CounterApp {
+    async fn incr(&self, key: String) -> u64 {
+        let mut counts = self.counts.lock().unwrap();
+        let x = counts.entry(key).or_insert(0);
+        *x += 1;
+        *x
+    }
+    async fn total(&self, wait: Duration) -> u64 {
+        tokio::time::sleep(wait).await;
+        self.counts.lock().unwrap().values().sum()
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broadcast() {
+    let mut chans = vec![];
+    for _ in 0..3 {
+        let app = CounterApp {
+            counts: Mutex::new(HashMap::new()),
+        };
+        let (chan, server) = ServerBuilder::new(CounterService::new(app)).build();
+        ::tokio::spawn(server.serve(TokioExecutor));
+        chans.push(chan);
+    }
+    // The last partition is gone.
+    let (chan, server) = ServerBuilder::new(CounterService::new(CounterApp {
+        counts: Mutex::new(HashMap::new()),
+    }))
+    .build();
+    drop(server);
+    chans.push(chan);
+
+    let mut cli = CounterShardedClient::new(chans);
+    for i in 0..30 {
+        let key = format!("key-{}", i % 5);
+        // Skip the keys of the broken partition.
+        if CounterRequest::incr(key.clone()).partition(4) != Some(3) {
+            cli.incr(key).await;
+        }
+    }
+
+    let reps = cli
+        .broadcast_total(Duration::ZERO, Duration::from_secs(1))
+        .await;
+    assert_eq!(reps.len(), 4);
+    assert!(matches!(reps[3], Err(BroadcastError::Service(_))));
+    let sum: u64 = reps.iter().filter_map(|x| x.as_ref().ok()).sum();
+    let expected = (0..30)
+        .filter(|i| CounterRequest::incr(format!("key-{}", i % 5)).partition(4) != Some(3))
+        .count();
+    assert_eq!(sum, expected as u64);
+
+    // The partitions too slow to respond time out.
+    let reps = cli
+        .broadcast_total(Duration::from_secs(10), Duration::from_millis(100))
+        .await;
+    assert!(reps[..3]
+        .iter()
+        .all(|x| matches!(x, Err(BroadcastError::Timeout))));
+}
+
+/// Not `Clone`, which only the broadcast methods need.
+pub struct Doc(u64);
+
+#[norpc::service]
+trait Search {
+    fn put(doc: Doc);
+    // The argument named `timeout` doesn't collide with the deadline.
+    #[broadcast]
+    fn search(timeout: Duration) -> Vec<u64>;
+}
+struct SearchApp {
+    docs: Mutex<Vec<u64>>,
+    done: Arc<AtomicUsize>,
+}
+#[norpc::async_trait]
+impl Search for SearchApp {
+    async fn put(&self, doc: Doc) {
+        self.docs.lock().unwrap().push(doc.0);
+    }
+    async fn search(&self, timeout: Duration) -> Vec<u64> {
+        tokio::time::sleep(timeout).await;
+        self.done.fetch_add(1, Ordering::SeqCst);
+        self.docs.lock().unwrap().clone()
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broadcast_without_shard_key() {
+    let done = Arc::new(AtomicUsize::new(0));
+    let mut chans = vec![];
+    for _ in 0..3 {
+        let app = SearchApp {
+            docs: Mutex::new(vec![]),
+            done: done.clone(),
+        };
+        let (chan, server) = ServerBuilder::new(SearchService::new(app)).build();
+        ::tokio::spawn(server.serve(TokioExecutor));
+        chans.push(chan);
+    }
+    let cli = SearchShardedClient::new(chans);
+    for i in 0..3 {
+        cli.partition(i).put(Doc(i as u64)).await;
+    }
+    let reps = cli
+        .broadcast_search(Duration::ZERO, Duration::from_secs(1))
+        .await;
+    let docs: Vec<u64> = reps.into_iter().flat_map(|x| x.unwrap()).collect();
+    assert_eq!(docs, vec![0, 1, 2]);
+    assert_eq!(done.load(Ordering::SeqCst), 3);
+
+    // The calls which miss the deadline are cancelled on the servers.
+    let reps = cli
+        .broadcast_search(Duration::from_millis(300), Duration::from_millis(50))
+        .await;
+    assert!(reps
+        .iter()
+        .all(|x| matches!(x, Err(BroadcastError::Timeout))));
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(done.load(Ordering::SeqCst), 3);
+}
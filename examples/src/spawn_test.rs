@@ -0,0 +1,50 @@
+#[norpc::service(spawn_test)]
+trait Counter {
+    fn add(n: u64) -> u64;
+    fn panic();
+    fn panic_later(ms: u64);
+}
+#[derive(Default)]
+struct CounterApp {
+    n: std::sync::atomic::AtomicU64,
+}
+#[norpc::async_trait]
+impl Counter for CounterApp {
+    async fn add(&self, n: u64) -> u64 {
+        self.n.fetch_add(n, std::sync::atomic::Ordering::SeqCst) + n
+    }
+    async fn panic(&self) {
+        panic!("I am panicked!");
+    }
+    async fn panic_later(&self, ms: u64) {
+        tokio::time::sleep(std::time::Duration::from_millis(ms)).await;
+        panic!("I am panicked!");
+    }
+}
+
+#[tokio::test]
+async fn test_spawn_test() {
+    let (mut cli, guard) = CounterClient::spawn_test(CounterApp::default());
+    assert_eq!(cli.add(1).await, 1);
+    assert_eq!(cli.add(2).await, 3);
+    assert!(!guard.panicked());
+}
+
+#[tokio::test]
+#[should_panic(expected = "a handler panicked in the server")]
+async fn test_spawn_test_panic() {
+    let (mut cli, _guard) = CounterClient::spawn_test(CounterApp::default());
+    // The client panics too because the reply is lost.
+    tokio::spawn(async move { cli.panic().await }).await.ok();
+}
+
+#[tokio::test]
+#[should_panic(expected = "a handler panicked in the server")]
+async fn test_spawn_test_panic_in_flight() {
+    let (mut cli, guard) = CounterClient::spawn_test(CounterApp::default());
+    tokio::spawn(async move { cli.panic_later(200).await });
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    assert!(!guard.panicked());
+    // The guard waits for the handler in flight instead of aborting it.
+    drop(guard);
+}
@@ -0,0 +1,175 @@
+use norpc::runtime::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tower::ServiceExt;
+
+#[norpc::service]
+trait Fragile {
+    fn instance() -> u64;
+    fn crash();
+    fn hang();
+    fn slow();
+}
+#[derive(Default)]
+struct FragileApp {
+    instance: u64,
+    log: Arc<Mutex<Vec<String>>>,
+}
+#[norpc::async_trait]
+impl Fragile for FragileApp {
+    async fn instance(&self) -> u64 {
+        self.instance
+    }
+    async fn crash(&self) {
+        panic!("crashed");
+    }
+    async fn hang(&self) {
+        futures::future::pending::<()>().await;
+    }
+    async fn slow(&self) {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        self.log
+            .lock()
+            .unwrap()
+            .push(format!("slow {}", self.instance));
+    }
+}
+#[norpc::async_trait]
+impl norpc::Lifecycle for FragileApp {
+    async fn on_start(&self) {
+        self.log
+            .lock()
+            .unwrap()
+            .push(format!("start {}", self.instance));
+    }
+    async fn on_stop(&self) {
+        self.log
+            .lock()
+            .unwrap()
+            .push(format!("stop {}", self.instance));
+    }
+}
+
+type FragileChannel = Channel<FragileRequest, FragileResponse>;
+
+fn spawn_supervisor(strategy: RestartStrategy) -> FragileChannel {
+    let n = Arc::new(AtomicU64::new(0));
+    let (chan, server) = Supervisor::new(move || {
+        let instance = n.fetch_add(1, Ordering::SeqCst);
+        FragileService::new(FragileApp {
+            instance,
+            ..Default::default()
+        })
+    })
+    .strategy(strategy)
+    .build();
+    ::tokio::spawn(server.serve(TokioExecutor));
+    chan
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_supervisor_restart() {
+    let chan = spawn_supervisor(RestartStrategy::default());
+    let mut cli = FragileClient::new(chan.clone());
+    assert_eq!(cli.instance().await, 0);
+
+    let hang = tokio::spawn(chan.clone().oneshot(FragileRequest::hang()));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let e = chan
+        .clone()
+        .oneshot(FragileRequest::crash())
+        .await
+        .err()
+        .unwrap();
+    assert!(e.downcast_ref::<Restarted>().is_some());
+    // The pending calls fail too.
+    let e = hang.await.unwrap().err().unwrap();
+    assert!(e.downcast_ref::<Restarted>().is_some());
+
+    // The channel is still valid and reaches the new service.
+    assert_eq!(cli.instance().await, 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_supervisor_give_up() {
+    let chan = spawn_supervisor(RestartStrategy::OneForOne {
+        max_restarts: 1,
+        within: Duration::from_secs(60),
+    });
+    let e = chan
+        .clone()
+        .oneshot(FragileRequest::crash())
+        .await
+        .err()
+        .unwrap();
+    assert!(e.downcast_ref::<Restarted>().is_some());
+    let mut cli = FragileClient::new(chan.clone());
+    assert_eq!(cli.instance().await, 1);
+
+    // The second crash within the window exceeds the limit.
+    chan.clone().oneshot(FragileRequest::crash()).await.ok();
+    assert!(chan.oneshot(FragileRequest::instance()).await.is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_supervisor_lifecycle() {
+    let log = Arc::new(Mutex::new(vec![]));
+    let n = Arc::new(AtomicU64::new(0));
+    let (chan, server) = {
+        let log = log.clone();
+        Supervisor::new(move || {
+            FragileService::new(FragileApp {
+                instance: n.fetch_add(1, Ordering::SeqCst),
+                log: log.clone(),
+            })
+        })
+        .lifecycle()
+        .build()
+    };
+    let handle = server.handle();
+    let server = ::tokio::spawn(server.serve(TokioExecutor));
+
+    chan.clone().oneshot(FragileRequest::crash()).await.ok();
+    let mut cli = FragileClient::new(chan.clone());
+    let slow = ::tokio::spawn(async move { cli.slow().await });
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    handle.shutdown();
+    server.await.unwrap();
+    // on_start is called on the first service and on_stop on the last one
+    // after the handler in flight finishes.
+    assert_eq!(*log.lock().unwrap(), vec!["start 0", "slow 1", "stop 1"]);
+    slow.await.unwrap();
+
+    // The requests after shutdown fail.
+    assert!(chan.oneshot(FragileRequest::instance()).await.is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_supervisor_replace() {
+    let n = Arc::new(AtomicU64::new(0));
+    let (chan, server) = Supervisor::new(move || {
+        FragileService::new(FragileApp {
+            instance: n.fetch_add(1, Ordering::SeqCst),
+            ..Default::default()
+        })
+    })
+    .build();
+    let handle = server.handle();
+    ::tokio::spawn(server.serve(TokioExecutor));
+
+    let mut cli = FragileClient::new(chan.clone());
+    assert_eq!(cli.instance().await, 0);
+    handle
+        .replace(FragileService::new(FragileApp {
+            instance: 10,
+            ..Default::default()
+        }))
+        .ok();
+    assert_eq!(cli.instance().await, 10);
+
+    // The replaced service is restarted by the factory.
+    chan.clone().oneshot(FragileRequest::crash()).await.ok();
+    assert_eq!(cli.instance().await, 1);
+}
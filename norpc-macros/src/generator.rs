@@ -2,8 +2,30 @@ use super::Service;
 
 pub struct Generator {
     pub no_send: bool,
+    pub json: bool,
+    pub mock: bool,
+    pub serde: bool,
+    pub blocking: bool,
+    pub actor: bool,
+    pub spawn_test: bool,
+    pub client_impl: bool,
 }
 impl Generator {
+    fn receiver(&self) -> &'static str {
+        if self.actor {
+            "&mut self"
+        } else {
+            "&self"
+        }
+    }
+    fn derive_serde(&self) -> &'static str {
+        if self.serde {
+            "#[derive(norpc::serde::Serialize, norpc::serde::Deserialize)]
+            #[serde(crate = \"norpc::serde\")]"
+        } else {
+            ""
+        }
+    }
     fn generate_request(&self, svc: &Service) -> String {
         let mut variants = vec![];
         for fun in &svc.functions {
@@ -16,11 +38,13 @@ impl Generator {
         format!(
             "
         #[allow(non_camel_case_types)]
+        {derive_serde}
         pub enum {svc_name}Request {{
 		{}
 	}}",
             itertools::join(variants, ","),
             svc_name = svc.name,
+            derive_serde = self.derive_serde(),
         )
     }
     fn generate_response(&self, svc: &Service) -> String {
@@ -31,11 +55,13 @@ impl Generator {
         format!(
             "
         #[allow(non_camel_case_types)]
+        {derive_serde}
         pub enum {svc_name}Response {{
 		{}
 	}}",
             itertools::join(variants, ","),
             svc_name = svc.name,
+            derive_serde = self.derive_serde(),
         )
     }
     fn generate_client_struct(&self, svc: &Service) -> String {
@@ -53,36 +79,66 @@ impl Generator {
         format!(
             "
 	pub struct {svc_name}Service<App: {svc_name}> {{
-		app: std::sync::Arc<App>
+		app: {app_type},
+		{pool}
 	}}
 	",
             svc_name = svc.name,
+            // The handlers of blocking services run on this pool.
+            pool = if self.blocking {
+                "pool: norpc::blocking::Pool,"
+            } else {
+                ""
+            },
+            app_type = if self.actor {
+                "norpc::actor::Slot<App>"
+            } else {
+                "std::sync::Arc<App>"
+            },
         )
     }
     fn generate_trait(&self, svc: &Service) -> String {
         let mut methods = vec![];
         for fun in &svc.functions {
-            let mut params = vec!["&self".to_owned()];
+            let mut params = vec![self.receiver().to_owned()];
             for param in &fun.inputs {
                 params.push(format!("{}:{}", param.var_name, param.typ_name,));
             }
             let params = itertools::join(params, ",");
             methods.push(format!(
-                "async fn {}({}) -> {};",
-                fun.name, &params, fun.output,
+                "{}fn {}({}) -> {};",
+                if self.blocking { "" } else { "async " },
+                fun.name,
+                &params,
+                fun.output,
             ));
         }
         format!(
             "
-		#[norpc::async_trait{no_send_marker}]
-		pub trait {svc_name}: Sync {no_send_constraint} {{
+		{async_trait}
+		pub trait {svc_name} {thread_safe} {{
 			{}
 		}}
 		",
             itertools::join(methods, ""),
             svc_name = svc.name,
-            no_send_marker = if self.no_send { "(?Send)" } else { "" },
-            no_send_constraint = if self.no_send { "" } else { "+ Send " },
+            async_trait = if self.blocking {
+                ""
+            } else if self.no_send {
+                "#[norpc::async_trait(?Send)]"
+            } else {
+                "#[norpc::async_trait]"
+            },
+            // Non-Send services are neither required to be Sync
+            // so they can hold Rc or RefCell.
+            // Actors needn't be Sync because the handlers have exclusive access.
+            thread_safe = if self.no_send {
+                ""
+            } else if self.actor {
+                ": Send"
+            } else {
+                ": Sync + Send"
+            },
         )
     }
     fn generate_client_impl(&self, svc: &Service) -> String {
@@ -102,9 +158,10 @@ impl Generator {
 
             let f = format!(
                 "
+        {deprecated}
 		pub async fn {fun_name}({params}) -> {output} {{
             norpc::poll_fn(|ctx| self.svc.poll_ready(ctx)).await.ok();
-			let rep = self.svc.call({}Request::{fun_name}({req_params})).await;
+			let rep = self.svc.call({svc_name}Request::{fun_name}({req_params})).await;
 			match rep {{
 				Ok({svc_name}Response::{fun_name}(v)) => v,
                 #[allow(unreachable_patterns)]
@@ -117,6 +174,7 @@ impl Generator {
                 params = params,
                 output = fun.output,
                 req_params = req_params,
+                deprecated = fun.deprecated.as_deref().unwrap_or(""),
             );
             methods.push(f);
         }
@@ -133,6 +191,285 @@ impl Generator {
             svc_name = svc.name,
         )
     }
+    fn generate_blocking_client(&self, svc: &Service) -> String {
+        let mut methods = vec![];
+        for fun in &svc.functions {
+            let mut params = vec!["&mut self".to_owned()];
+            for p in &fun.inputs {
+                params.push(format!("{}:{}", p.var_name, p.typ_name,));
+            }
+            let params = itertools::join(params, ",");
+
+            let mut req_params = vec![];
+            for p in &fun.inputs {
+                req_params.push(p.var_name.to_owned());
+            }
+            let req_params = itertools::join(req_params, ",");
+
+            methods.push(format!(
+                "
+        {deprecated}
+		pub fn {fun_name}({params}) -> {output} {{
+			let rep = self.svc.call_blocking({svc_name}Request::{fun_name}({req_params}));
+			match rep {{
+				Ok({svc_name}Response::{fun_name}(v)) => v,
+                #[allow(unreachable_patterns)]
+				_ => unreachable!(),
+			}}
+		}}
+		",
+                svc_name = svc.name,
+                fun_name = fun.name,
+                params = params,
+                output = fun.output,
+                req_params = req_params,
+                deprecated = fun.deprecated.as_deref().unwrap_or(""),
+            ));
+        }
+        format!(
+            "
+    /// Client whose methods block the current thread until the response arrives.
+    #[derive(Clone)]
+	pub struct {svc_name}BlockingClient<Svc> {{
+		svc: Svc
+	}}
+	impl<Svc: norpc::BlockingCall<{svc_name}Request, Response = {svc_name}Response>> {svc_name}BlockingClient<Svc> {{
+		pub fn new(svc: Svc) -> Self {{
+			Self {{ svc }}
+		}}
+		{}
+	}}
+	",
+            itertools::join(methods, ""),
+            svc_name = svc.name,
+        )
+    }
+    /// The client implements the service trait so the code written against the trait
+    /// can take either the app itself or a client to it.
+    fn generate_client_trait_impl(&self, svc: &Service) -> String {
+        if !self.client_impl {
+            return String::new();
+        }
+        let mut methods = vec![];
+        for fun in &svc.functions {
+            let mut params = vec![self.receiver().to_owned()];
+            for p in &fun.inputs {
+                params.push(format!("{}:{}", p.var_name, p.typ_name,));
+            }
+            let params = itertools::join(params, ",");
+
+            let mut req_params = vec!["&mut cli".to_owned()];
+            for p in &fun.inputs {
+                req_params.push(p.var_name.to_owned());
+            }
+            let req_params = itertools::join(req_params, ",");
+
+            methods.push(format!(
+                "
+        {allow_deprecated}
+		async fn {fun_name}({params}) -> {output} {{
+			let mut cli = self.clone();
+			Self::{fun_name}({req_params}).await
+		}}
+		",
+                fun_name = fun.name,
+                params = params,
+                output = fun.output,
+                req_params = req_params,
+                allow_deprecated = if fun.deprecated.is_some() {
+                    "#[allow(deprecated)]"
+                } else {
+                    ""
+                },
+            ));
+        }
+        format!(
+            "
+    #[norpc::async_trait{no_send_marker}]
+	impl<Svc> {svc_name} for {svc_name}Client<Svc>
+	where
+		Svc: norpc::Service<{svc_name}Request, Response = {svc_name}Response> + Clone {thread_safe} + 'static,
+		Svc::Future: 'static {no_send},
+	{{
+		{}
+	}}
+	",
+            itertools::join(methods, ""),
+            svc_name = svc.name,
+            no_send_marker = if self.no_send { "(?Send)" } else { "" },
+            no_send = if self.no_send { "" } else { "+ Send" },
+            thread_safe = if self.no_send { "" } else { "+ Sync + Send" },
+        )
+    }
+    /// Requests of the methods with a `#[shard_key]` argument are routed
+    /// to the partition the key hashes to
+    /// and the methods marked `#[broadcast]` are called on all the partitions.
+    fn generate_sharded_client(&self, svc: &Service) -> String {
+        let keyed = svc
+            .functions
+            .iter()
+            .filter(|fun| fun.inputs.iter().any(|p| p.shard_key))
+            .collect::<Vec<_>>();
+        let broadcast = svc
+            .functions
+            .iter()
+            .filter(|fun| fun.broadcast)
+            .collect::<Vec<_>>();
+        if keyed.is_empty() && broadcast.is_empty() {
+            return String::new();
+        }
+        let mut partition_arms = vec![];
+        let mut methods = vec![];
+        for fun in &broadcast {
+            let mut params = vec!["&self".to_owned()];
+            for p in &fun.inputs {
+                params.push(format!("{}:{}", p.var_name, p.typ_name,));
+            }
+            // Not to collide with the arguments.
+            params.push("__norpc_timeout: std::time::Duration".to_owned());
+            let params = itertools::join(params, ",");
+
+            // The arguments are cloned for each partition.
+            let mut req_params = vec![];
+            for p in &fun.inputs {
+                req_params.push(format!("{}.clone()", p.var_name));
+            }
+            let req_params = itertools::join(req_params, ",");
+
+            methods.push(format!(
+                "
+        /// Call `{fun_name}` on all the partitions.
+        /// The results are in the order of the partitions
+        /// and the calls not finished in `__norpc_timeout` result in `Timeout`.
+        /// Each call goes through a clone of the partition's service which is dropped
+        /// on the timeout, so a channel cancels the call on the server.
+        {deprecated}
+		pub async fn broadcast_{fun_name}({params}) -> Vec<std::result::Result<{output}, norpc::shard::BroadcastError<Svc::Error>>> where Svc: Clone {{
+			let calls = self.shards.iter().map(|svc| {{
+				let mut svc = svc.clone();
+				let req = {svc_name}Request::{fun_name}({req_params});
+				async move {{
+					norpc::poll_fn(|ctx| svc.poll_ready(ctx)).await?;
+					match svc.call(req).await? {{
+						{svc_name}Response::{fun_name}(v) => Ok(v),
+						#[allow(unreachable_patterns)]
+						_ => unreachable!(),
+					}}
+				}}
+			}}).collect::<Vec<_>>();
+			norpc::shard::gather(calls, __norpc_timeout).await
+		}}
+		",
+                svc_name = svc.name,
+                fun_name = fun.name,
+                params = params,
+                output = fun.output,
+                req_params = req_params,
+                deprecated = fun.deprecated.as_deref().unwrap_or(""),
+            ));
+        }
+        for fun in &keyed {
+            let mut pats = vec![];
+            for p in &fun.inputs {
+                pats.push(if p.shard_key { "key" } else { "_" });
+            }
+            partition_arms.push(format!(
+                "{}Request::{}({}) => Some(norpc::shard::partition(key, n))",
+                svc.name,
+                fun.name,
+                itertools::join(pats, ","),
+            ));
+
+            let mut params = vec!["&mut self".to_owned()];
+            for p in &fun.inputs {
+                params.push(format!("{}:{}", p.var_name, p.typ_name,));
+            }
+            let params = itertools::join(params, ",");
+
+            let mut req_params = vec![];
+            for p in &fun.inputs {
+                req_params.push(p.var_name.to_owned());
+            }
+            let req_params = itertools::join(req_params, ",");
+
+            methods.push(format!(
+                "
+        {deprecated}
+		pub async fn {fun_name}({params}) -> {output} {{
+			let req = {svc_name}Request::{fun_name}({req_params});
+			let i = req.partition(self.shards.len()).unwrap();
+			let svc = &mut self.shards[i];
+            norpc::poll_fn(|ctx| svc.poll_ready(ctx)).await.ok();
+			let rep = svc.call(req).await;
+			match rep {{
+				Ok({svc_name}Response::{fun_name}(v)) => v,
+                #[allow(unreachable_patterns)]
+				_ => unreachable!(),
+			}}
+		}}
+		",
+                svc_name = svc.name,
+                fun_name = fun.name,
+                params = params,
+                output = fun.output,
+                req_params = req_params,
+                deprecated = fun.deprecated.as_deref().unwrap_or(""),
+            ));
+        }
+        let partition_impl = if keyed.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "
+    impl {svc_name}Request {{
+        /// Partition out of `n` this request goes to or `None` if the method has no shard key.
+        /// Panics if `n` is 0 and the method has a shard key.
+        pub fn partition(&self, n: usize) -> Option<usize> {{
+            match self {{
+                {},
+                #[allow(unreachable_patterns)]
+                _ => None,
+            }}
+        }}
+    }}
+    ",
+                itertools::join(partition_arms, ","),
+                svc_name = svc.name,
+            )
+        };
+        format!(
+            "
+    {partition_impl}
+    /// Client which routes the requests to the partitions by the shard keys.
+    ///
+    /// The methods marked `#[broadcast]` are called on all the partitions by `broadcast_<method>`.
+    /// The other methods without a shard key are called
+    /// on one of the partitions through [`{svc_name}ShardedClient::partition`].
+    #[derive(Clone)]
+	pub struct {svc_name}ShardedClient<Svc> {{
+		shards: Vec<Svc>
+	}}
+	impl<Svc: norpc::Service<{svc_name}Request, Response = {svc_name}Response>> {svc_name}ShardedClient<Svc> {{
+		pub fn new(shards: Vec<Svc>) -> Self {{
+            assert!(!shards.is_empty(), \"at least one partition is needed\");
+			Self {{ shards }}
+		}}
+        /// Number of the partitions.
+        pub fn partitions(&self) -> usize {{
+            self.shards.len()
+        }}
+        /// Client to the `i`-th partition.
+        pub fn partition(&self, i: usize) -> {svc_name}Client<Svc> where Svc: Clone {{
+            {svc_name}Client::new(self.shards[i].clone())
+        }}
+		{}
+	}}
+	",
+            itertools::join(methods, ""),
+            svc_name = svc.name,
+            partition_impl = partition_impl,
+        )
+    }
     fn generate_server_impl(&self, svc: &Service) -> String {
         let mut match_arms = vec![];
         for fun in &svc.functions {
@@ -145,13 +482,21 @@ impl Generator {
             let a = format!(
                 "
 		{svc_name}Request::{fun_name}({req_params}) => {{
-			let rep = app.{fun_name}({req_params}).await;
+			let rep = {call};
 			Ok({svc_name}Response::{fun_name}(rep))
 		}}
 		",
                 svc_name = svc.name,
                 fun_name = fun.name,
                 req_params = req_params,
+                call = if self.blocking {
+                    format!(
+                        "norpc::blocking::run(&pool, move || app.{}({})).await.map_err(|_| ())?",
+                        fun.name, req_params
+                    )
+                } else {
+                    format!("app.{}({}).await", fun.name, req_params)
+                },
             );
 
             match_arms.push(a);
@@ -161,7 +506,13 @@ impl Generator {
         "
 	impl<App: {svc_name}> {svc_name}Service<App> {{
 		pub fn new(app: App) -> Self {{
-			Self {{ app: std::sync::Arc::new(app) }}
+			Self {{ app: {new_app}, {default_pool} }}
+		}}
+		{with_pool}
+	}}
+	impl<App: {svc_name}> Clone for {svc_name}Service<App> {{
+		fn clone(&self) -> Self {{
+			Self {{ app: self.app.clone(), {clone_pool} }}
 		}}
 	}}
     impl<App: {svc_name} + 'static {no_send}> norpc::Service<{svc_name}Request> for {svc_name}Service<App> {{
@@ -175,8 +526,10 @@ impl Generator {
             Ok(()).into()
         }}
 		fn call(&mut self, req: {svc_name}Request) -> Self::Future {{
-			let app = self.app.clone();
+			{take_app}
+			{take_pool}
             Box::pin(async move {{
+                {wait_app}
                 match req {{
                     {}
                 }}
@@ -187,18 +540,332 @@ impl Generator {
         itertools::join(match_arms, ","),
         svc_name = svc.name,
         no_send = if self.no_send { "" } else { "+ Send" },
+        default_pool = if self.blocking {
+            "pool: Default::default()"
+        } else {
+            ""
+        },
+        with_pool = if self.blocking {
+            "
+        /// Run the handlers on `pool` instead of the default one.
+        pub fn with_pool(app: App, pool: norpc::blocking::Pool) -> Self {
+            Self { pool, ..Self::new(app) }
+        }"
+        } else {
+            ""
+        },
+        clone_pool = if self.blocking {
+            "pool: self.pool.clone()"
+        } else {
+            ""
+        },
+        take_pool = if self.blocking {
+            "let pool = self.pool.clone();"
+        } else {
+            ""
+        },
+        new_app = if self.actor {
+            "norpc::actor::Slot::new(app)"
+        } else {
+            "std::sync::Arc::new(app)"
+        },
+        // The ticket is taken here so that the requests are processed
+        // one by one in the order they are called.
+        take_app = if self.actor {
+            "let ticket = self.app.ticket();"
+        } else {
+            "let app = self.app.clone();"
+        },
+        wait_app = if self.actor {
+            "let mut app = ticket.await;"
+        } else {
+            ""
+        },
     )
     }
+    /// The service forwards the lifecycle hooks to the app.
+    fn generate_lifecycle_impl(&self, svc: &Service) -> String {
+        // The hooks are Send futures.
+        if self.no_send {
+            return String::new();
+        }
+        format!(
+            "
+    #[norpc::async_trait]
+    impl<App: {svc_name} + norpc::Lifecycle {sync}> norpc::Lifecycle for {svc_name}Service<App> {{
+        async fn on_start(&self) {{
+            {app}.on_start().await
+        }}
+        async fn on_stop(&self) {{
+            {app}.on_stop().await
+        }}
+    }}
+	",
+            svc_name = svc.name,
+            // Actor apps needn't be Sync but the hooks take &self.
+            sync = if self.actor { "+ Sync" } else { "" },
+            app = if self.actor {
+                "self.app.take().await"
+            } else {
+                "self.app"
+            },
+        )
+    }
+    /// FNV-1a hash over the method ids and names and the argument and return type tokens.
+    /// Argument names are left out because renaming them doesn't change the messages.
+    fn fingerprint(&self, svc: &Service) -> u64 {
+        let mut sig = svc.name.clone();
+        for fun in &svc.functions {
+            let mut params = vec![];
+            for p in &fun.inputs {
+                params.push(p.typ_name.clone());
+            }
+            sig.push_str(&format!(
+                ";{}:{}({})->{}",
+                fun.id,
+                fun.name,
+                itertools::join(params, ","),
+                fun.output
+            ));
+        }
+        let mut h: u64 = 0xcbf29ce484222325;
+        for b in sig.bytes() {
+            h ^= b as u64;
+            h = h.wrapping_mul(0x100000001b3);
+        }
+        h
+    }
+    fn generate_schema(&self, svc: &Service) -> String {
+        let mut methods = vec![];
+        let mut id_arms = vec![];
+        let mut name_arms = vec![];
+        for fun in &svc.functions {
+            methods.push(format!("({}, \"{}\")", fun.id, fun.name));
+            id_arms.push(format!(
+                "{}Request::{}(..) => {}",
+                svc.name, fun.name, fun.id
+            ));
+            name_arms.push(format!(
+                "{}Request::{}(..) => \"{}\"",
+                svc.name, fun.name, fun.name
+            ));
+        }
+        format!(
+            "
+    impl norpc::Schema for {svc_name}Request {{
+        const NAME: &'static str = \"{svc_name}\";
+        const FINGERPRINT: u64 = {fingerprint};
+        const METHODS: &'static [(u32, &'static str)] = &[{}];
+        fn method_id(&self) -> u32 {{
+            match *self {{
+                {}
+            }}
+        }}
+        fn method_name(&self) -> &'static str {{
+            match *self {{
+                {}
+            }}
+        }}
+    }}
+	",
+            itertools::join(methods, ","),
+            itertools::join(id_arms, ","),
+            itertools::join(name_arms, ","),
+            svc_name = svc.name,
+            fingerprint = self.fingerprint(svc),
+        )
+    }
+    fn generate_json(&self, svc: &Service) -> String {
+        if !self.json {
+            return String::new();
+        }
+        let mut decode_arms = vec![];
+        let mut encode_arms = vec![];
+        for fun in &svc.functions {
+            let mut args = vec![];
+            for (i, p) in fun.inputs.iter().enumerate() {
+                args.push(format!("params.take({}, \"{}\")?", i, p.var_name));
+            }
+            decode_arms.push(format!(
+                "
+            \"{fun_name}\" => {{
+                #[allow(unused_mut, unused_variables)]
+                let mut params = norpc::json::Params::new(params)?;
+                Ok({svc_name}Request::{fun_name}({args}))
+            }}
+            ",
+                svc_name = svc.name,
+                fun_name = fun.name,
+                args = itertools::join(args, ","),
+            ));
+            encode_arms.push(format!(
+                "{svc_name}Response::{fun_name}(v) => norpc::json::to_value(v)",
+                svc_name = svc.name,
+                fun_name = fun.name,
+            ));
+        }
+        format!(
+            "
+    impl norpc::json::JsonRequest for {svc_name}Request {{
+        type Response = {svc_name}Response;
+        fn from_json(method: &str, params: norpc::json::Value) -> std::result::Result<Self, norpc::json::Error> {{
+            match method {{
+                {}
+                _ => Err(norpc::json::Error::unknown_method::<Self>(method)),
+            }}
+        }}
+        fn response_to_json(rep: {svc_name}Response) -> std::result::Result<norpc::json::Value, norpc::json::Error> {{
+            match rep {{
+                {}
+            }}
+        }}
+    }}
+	",
+            itertools::join(decode_arms, ""),
+            itertools::join(encode_arms, ","),
+            svc_name = svc.name,
+        )
+    }
+    fn generate_mock(&self, svc: &Service) -> String {
+        if !self.mock {
+            return String::new();
+        }
+        let mut fields = vec![];
+        let mut inits = vec![];
+        let mut expects = vec![];
+        let mut checkpoints = vec![];
+        let mut match_arms = vec![];
+        for fun in &svc.functions {
+            let mut types = vec![];
+            let mut req_params = vec![];
+            for p in &fun.inputs {
+                types.push(format!("{},", p.typ_name));
+                req_params.push(format!("{},", p.var_name));
+            }
+            let args_type = format!("({})", types.concat());
+            let args = format!("({})", req_params.concat());
+            fields.push(format!(
+                "{}: norpc::testing::Method<{}, {}>",
+                fun.name, args_type, fun.output
+            ));
+            inits.push(format!(
+                "{fun_name}: norpc::testing::Method::new(\"{svc_name}::{fun_name}\".to_owned())",
+                svc_name = svc.name,
+                fun_name = fun.name,
+            ));
+            expects.push(format!(
+                "
+        pub fn expect_{fun_name}(&self) -> norpc::testing::Expectation<{args_type}, {output}> {{
+            self.methods.{fun_name}.expect()
+        }}
+        ",
+                fun_name = fun.name,
+                args_type = args_type,
+                output = fun.output,
+            ));
+            checkpoints.push(format!("self.methods.{}.checkpoint();", fun.name));
+            match_arms.push(format!(
+                "{svc_name}Request::{fun_name}({req_params}) => {svc_name}Response::{fun_name}(self.methods.{fun_name}.call({args}))",
+                svc_name = svc.name,
+                fun_name = fun.name,
+                req_params = req_params.concat(),
+                args = args,
+            ));
+        }
+        format!(
+            "
+    /// Mock of the service to set expectations on the calls in unit tests.
+    #[derive(Clone)]
+    pub struct {svc_name}Mock {{
+        methods: std::sync::Arc<{svc_name}MockMethods>,
+    }}
+    struct {svc_name}MockMethods {{
+        {}
+    }}
+    impl {svc_name}Mock {{
+        pub fn new() -> Self {{
+            Self {{
+                methods: std::sync::Arc::new({svc_name}MockMethods {{
+                    {}
+                }}),
+            }}
+        }}
+        {}
+        /// Panic if an expectation is not satisfied.
+        pub fn checkpoint(&self) {{
+            {}
+        }}
+    }}
+    impl Default for {svc_name}Mock {{
+        fn default() -> Self {{
+            Self::new()
+        }}
+    }}
+    impl norpc::Service<{svc_name}Request> for {svc_name}Mock {{
+        type Response = {svc_name}Response;
+        type Error = ();
+        type Future = std::pin::Pin<Box<dyn std::future::Future<Output = std::result::Result<Self::Response, Self::Error>> {no_send}>>;
+        fn poll_ready(
+            &mut self,
+            _: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::result::Result<(), Self::Error>> {{
+            Ok(()).into()
+        }}
+        // The match is empty if the service has no methods.
+        #[allow(unreachable_code)]
+        fn call(&mut self, req: {svc_name}Request) -> Self::Future {{
+            let rep = match req {{
+                {}
+            }};
+            Box::pin(async move {{ Ok(rep) }})
+        }}
+    }}
+	",
+            itertools::join(fields, ","),
+            itertools::join(inits, ","),
+            itertools::join(expects, ""),
+            itertools::join(checkpoints, ""),
+            itertools::join(match_arms, ","),
+            svc_name = svc.name,
+            no_send = if self.no_send { "" } else { "+ Send" },
+        )
+    }
+    fn generate_spawn_test(&self, svc: &Service) -> String {
+        if !self.spawn_test {
+            return String::new();
+        }
+        format!(
+            "
+    impl {svc_name}Client<norpc::runtime::Channel<{svc_name}Request, {svc_name}Response>> {{
+        /// Spawn a server of `app` on a thread of its own and connect a client to it.
+        /// The server is shut down when the guard is dropped.
+        pub fn spawn_test(app: impl {svc_name} + 'static) -> (Self, norpc::testing::TestGuard) {{
+            let (chan, guard) = norpc::testing::spawn({svc_name}Service::new(app));
+            (Self::new(chan), guard)
+        }}
+    }}
+	",
+            svc_name = svc.name,
+        )
+    }
     pub(super) fn generate(&self, svc: Service) -> String {
         format!(
-            "{}{}{}{}{}{}{}",
+            "{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}",
             self.generate_request(&svc),
             self.generate_response(&svc),
+            self.generate_schema(&svc),
+            self.generate_json(&svc),
             self.generate_trait(&svc),
             self.generate_client_struct(&svc),
             self.generate_client_impl(&svc),
+            self.generate_client_trait_impl(&svc),
+            self.generate_blocking_client(&svc),
+            self.generate_sharded_client(&svc),
             self.generate_server_struct(&svc),
             self.generate_server_impl(&svc),
+            self.generate_lifecycle_impl(&svc),
+            self.generate_mock(&svc),
+            self.generate_spawn_test(&svc),
         )
     }
 }
@@ -1,5 +1,4 @@
 use proc_macro::TokenStream;
-use proc_macro2::*;
 use quote::quote;
 use std::str::FromStr;
 use syn::parse::{Parse, ParseStream, Result};
@@ -9,20 +8,84 @@ mod generator;
 
 struct Args {
     local: bool,
+    json: bool,
+    mock: bool,
+    serde: bool,
+    blocking: bool,
+    actor: bool,
+    spawn_test: bool,
+    client_impl: bool,
 }
 
 mod kw {
     syn::custom_keyword!(Send);
+    syn::custom_keyword!(json);
+    syn::custom_keyword!(mock);
+    syn::custom_keyword!(serde);
+    syn::custom_keyword!(blocking);
+    syn::custom_keyword!(actor);
+    syn::custom_keyword!(spawn_test);
+    syn::custom_keyword!(client_impl);
 }
 
 fn try_parse(input: ParseStream) -> Result<Args> {
-    if input.peek(Token![?]) {
-        input.parse::<Token![?]>()?;
-        input.parse::<kw::Send>()?;
-        Ok(Args { local: true })
-    } else {
-        Ok(Args { local: false })
+    let mut args = Args {
+        local: false,
+        json: false,
+        mock: false,
+        serde: false,
+        blocking: false,
+        actor: false,
+        spawn_test: false,
+        client_impl: false,
+    };
+    while !input.is_empty() {
+        if input.peek(Token![?]) {
+            input.parse::<Token![?]>()?;
+            input.parse::<kw::Send>()?;
+            args.local = true;
+        } else if input.peek(kw::json) {
+            input.parse::<kw::json>()?;
+            args.json = true;
+        } else if input.peek(kw::mock) {
+            input.parse::<kw::mock>()?;
+            args.mock = true;
+        } else if input.peek(kw::serde) {
+            input.parse::<kw::serde>()?;
+            args.serde = true;
+        } else if input.peek(kw::blocking) {
+            input.parse::<kw::blocking>()?;
+            args.blocking = true;
+        } else if input.peek(kw::actor) {
+            input.parse::<kw::actor>()?;
+            args.actor = true;
+        } else if input.peek(kw::spawn_test) {
+            input.parse::<kw::spawn_test>()?;
+            args.spawn_test = true;
+        } else if input.peek(kw::client_impl) {
+            input.parse::<kw::client_impl>()?;
+            args.client_impl = true;
+        } else {
+            return Err(input.error(
+                "expected `?Send`, `json`, `mock`, `serde`, `blocking`, `actor`, `spawn_test` or `client_impl`",
+            ));
+        }
+        if !input.is_empty() {
+            input.parse::<Token![,]>()?;
+        }
+    }
+    if args.local && args.blocking {
+        return Err(input.error("`blocking` can't be used with `?Send`"));
+    }
+    // The methods of the trait are synchronous but the client is async.
+    if args.blocking && args.client_impl {
+        return Err(input.error("`client_impl` can't be used with `blocking`"));
+    }
+    // The spawned server needs Send services.
+    if args.local && args.spawn_test {
+        return Err(input.error("`spawn_test` can't be used with `?Send`"));
     }
+    Ok(args)
 }
 
 impl Parse for Args {
@@ -36,9 +99,19 @@ impl Parse for Args {
 pub fn service(args: TokenStream, item: TokenStream) -> TokenStream {
     let args = parse_macro_input!(args as Args);
     let t = syn::parse::<ItemTrait>(item).unwrap();
-    let svc = parse_service(&t);
+    let svc = match parse_service(&t) {
+        Ok(svc) => svc,
+        Err(e) => return e.to_compile_error().into(),
+    };
     let generator = generator::Generator {
         no_send: args.local,
+        json: args.json,
+        mock: args.mock,
+        serde: args.serde,
+        blocking: args.blocking,
+        actor: args.actor,
+        spawn_test: args.spawn_test,
+        client_impl: args.client_impl,
     };
     let code = generator.generate(svc);
     TokenStream::from_str(&code).unwrap()
@@ -52,34 +125,84 @@ struct Service {
 #[derive(Debug)]
 struct Function {
     name: String,
+    id: u32,
     inputs: Vec<Parameter>,
     output: String,
+    deprecated: Option<String>,
+    /// Marked `#[broadcast]` to be called on all the partitions.
+    broadcast: bool,
 }
 #[derive(Debug)]
 struct Parameter {
     var_name: String,
     typ_name: String,
+    shard_key: bool,
 }
 
-fn parse_service(t: &ItemTrait) -> Service {
+fn parse_service(t: &ItemTrait) -> Result<Service> {
     let svc_name = {
         let x = &t.ident;
         quote!(#x).to_string()
     };
-    let mut functions = vec![];
+    let mut parsed = vec![];
     for f in &t.items {
-        functions.push(parse_func(f));
+        parsed.push((f, parse_func(f)?));
     }
-    Service {
+    // Positional ids would change when a method is inserted
+    // so they are only used when no method has an explicit one.
+    let explicit = parsed.iter().any(|(_, (_, id))| id.is_some());
+    let mut functions = vec![];
+    let mut ids = std::collections::HashMap::new();
+    for (i, (f, (mut fun, id))) in parsed.into_iter().enumerate() {
+        fun.id = match id {
+            Some(id) => id,
+            None if explicit => {
+                return Err(Error::new_spanned(
+                    f,
+                    "`#[id = N]` is required on every method once any method has it",
+                ))
+            }
+            None => i as u32,
+        };
+        if let Some(other) = ids.insert(fun.id, fun.name.clone()) {
+            return Err(Error::new_spanned(
+                f,
+                format!("method id {} is already used by `{}`", fun.id, other),
+            ));
+        }
+        functions.push(fun);
+    }
+    Ok(Service {
         name: svc_name,
         functions,
-    }
+    })
 }
-fn parse_func(f: &TraitItem) -> Function {
+/// Returns the function and its id if given by `#[id = N]`.
+fn parse_func(f: &TraitItem) -> Result<(Function, Option<u32>)> {
     match f {
         TraitItem::Method(m) => {
             let sig = &m.sig;
 
+            let mut id = None;
+            let mut deprecated = None;
+            let mut broadcast = false;
+            for attr in &m.attrs {
+                if attr.path.is_ident("id") {
+                    match attr.parse_meta()? {
+                        Meta::NameValue(MetaNameValue {
+                            lit: Lit::Int(n), ..
+                        }) => {
+                            id = Some(n.base10_parse()?);
+                        }
+                        meta => return Err(Error::new_spanned(meta, "expected `#[id = N]`")),
+                    }
+                } else if attr.path.is_ident("deprecated") {
+                    deprecated = Some(quote!(#attr).to_string());
+                } else if attr.path.is_ident("broadcast") {
+                    broadcast = true;
+                }
+            }
+
             let x = &sig.ident;
             let func_name = quote!(#x).to_string();
 
@@ -87,6 +210,13 @@ fn parse_func(f: &TraitItem) -> Function {
             for input in &sig.inputs {
                 match input {
                     FnArg::Typed(p) => {
+                        let shard_key = p.attrs.iter().any(|a| a.path.is_ident("shard_key"));
+                        if shard_key && inputs.iter().any(|x: &Parameter| x.shard_key) {
+                            return Err(Error::new_spanned(
+                                p,
+                                "only one argument can be `#[shard_key]`",
+                            ));
+                        }
                         let var_name = {
                             let x = &p.pat;
                             quote!(#x).to_string()
@@ -98,26 +228,34 @@ fn parse_func(f: &TraitItem) -> Function {
                         inputs.push(Parameter {
                             var_name,
                             typ_name: var_type,
+                            shard_key,
                         });
                     }
                     _ => unreachable!(),
                 }
             }
 
-            let output_ty;
-            match &sig.output {
-                ReturnType::Type(_, ty) => {
-                    output_ty = quote!(#ty).to_string();
-                }
-                ReturnType::Default => {
-                    output_ty = "()".to_string();
-                }
+            if broadcast && inputs.iter().any(|x| x.shard_key) {
+                return Err(Error::new_spanned(
+                    sig,
+                    "`#[broadcast]` method can't have a `#[shard_key]` argument",
+                ));
             }
-            Function {
+
+            let output_ty = match &sig.output {
+                ReturnType::Type(_, ty) => quote!(#ty).to_string(),
+                ReturnType::Default => "()".to_string(),
+            };
+            let fun = Function {
                 name: func_name,
+                // Assigned by the caller.
+                id: 0,
                 inputs,
                 output: output_ty,
-            }
+                deprecated,
+                broadcast,
+            };
+            Ok((fun, id))
         }
         // TODO ignore here to skip comments
         _ => unreachable!(),
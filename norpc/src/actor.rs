@@ -0,0 +1,129 @@
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+struct State<App> {
+    app: Option<App>,
+    next_ticket: u64,
+    queue: VecDeque<u64>,
+    wakers: HashMap<u64, Waker>,
+}
+impl<App> State<App> {
+    fn wake_front(&mut self) {
+        if let Some(front) = self.queue.front() {
+            if let Some(waker) = self.wakers.remove(front) {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// Slot to hold the app of an actor service.
+///
+/// A handler takes the app out of the slot and puts it back when it completes
+/// so the requests are processed one by one against the app.
+/// The handlers take the app in the order of their tickets.
+pub struct Slot<App> {
+    state: Arc<Mutex<State<App>>>,
+}
+impl<App> Clone for Slot<App> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+        }
+    }
+}
+impl<App> Slot<App> {
+    pub fn new(app: App) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                app: Some(app),
+                next_ticket: 0,
+                queue: VecDeque::new(),
+                wakers: HashMap::new(),
+            })),
+        }
+    }
+    /// Get in line to take the app.
+    pub fn ticket(&self) -> Ticket<App> {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_ticket;
+        state.next_ticket += 1;
+        state.queue.push_back(id);
+        Ticket {
+            id,
+            state: self.state.clone(),
+            done: false,
+        }
+    }
+    /// Wait for the turn and take the app.
+    pub async fn take(&self) -> Guard<App> {
+        self.ticket().await
+    }
+}
+
+/// Future to take the app in turn.
+/// Dropping it before completion gives up the turn.
+pub struct Ticket<App> {
+    id: u64,
+    state: Arc<Mutex<State<App>>>,
+    done: bool,
+}
+impl<App> Unpin for Ticket<App> {}
+impl<App> Future for Ticket<App> {
+    type Output = Guard<App>;
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Guard<App>> {
+        let mut state = self.state.lock().unwrap();
+        if state.queue.front() == Some(&self.id) && state.app.is_some() {
+            state.queue.pop_front();
+            let app = state.app.take();
+            drop(state);
+            self.done = true;
+            Poll::Ready(Guard {
+                app,
+                state: self.state.clone(),
+            })
+        } else {
+            state.wakers.insert(self.id, ctx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+impl<App> Drop for Ticket<App> {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.queue.retain(|x| *x != self.id);
+        state.wakers.remove(&self.id);
+        state.wake_front();
+    }
+}
+
+/// The app taken out of the slot. It is put back when dropped.
+pub struct Guard<App> {
+    app: Option<App>,
+    state: Arc<Mutex<State<App>>>,
+}
+impl<App> Deref for Guard<App> {
+    type Target = App;
+    fn deref(&self) -> &App {
+        self.app.as_ref().unwrap()
+    }
+}
+impl<App> DerefMut for Guard<App> {
+    fn deref_mut(&mut self) -> &mut App {
+        self.app.as_mut().unwrap()
+    }
+}
+impl<App> Drop for Guard<App> {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        state.app = self.app.take();
+        state.wake_front();
+    }
+}
@@ -0,0 +1,142 @@
+use futures::channel::oneshot;
+use futures::task::SpawnError;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Executor which also has a thread pool for blocking jobs.
+pub trait BlockingSpawn {
+    /// Run `f` on the blocking pool.
+    fn spawn_blocking_obj(&self, f: Box<dyn FnOnce() + Send>) -> Result<(), SpawnError>;
+}
+
+/// Runs each job on a new thread.
+///
+/// The number of threads is not bounded so prefer [`ThreadPool`]
+/// unless the jobs are few.
+pub struct ThreadPerJob;
+impl BlockingSpawn for ThreadPerJob {
+    fn spawn_blocking_obj(&self, f: Box<dyn FnOnce() + Send>) -> Result<(), SpawnError> {
+        std::thread::Builder::new()
+            .name("norpc-blocking".to_owned())
+            .spawn(f)
+            .map(|_| ())
+            .map_err(|_| SpawnError::shutdown())
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Runs the jobs on at most `max_threads` threads.
+///
+/// The threads are started as the jobs come and the jobs beyond
+/// `max_threads` wait in the queue.
+/// The threads exit when the pool is dropped.
+pub struct ThreadPool {
+    max_threads: usize,
+    threads: AtomicUsize,
+    /// Threads waiting for a job.
+    idle: Arc<AtomicUsize>,
+    tx: flume::Sender<Job>,
+    rx: flume::Receiver<Job>,
+}
+impl ThreadPool {
+    /// Panics if `max_threads` is 0.
+    pub fn new(max_threads: usize) -> Self {
+        assert!(max_threads > 0, "a thread pool needs at least one thread");
+        let (tx, rx) = flume::unbounded();
+        Self {
+            max_threads,
+            threads: AtomicUsize::new(0),
+            idle: Arc::new(AtomicUsize::new(0)),
+            tx,
+            rx,
+        }
+    }
+}
+impl BlockingSpawn for ThreadPool {
+    fn spawn_blocking_obj(&self, f: Job) -> Result<(), SpawnError> {
+        self.tx.send(f).map_err(|_| SpawnError::shutdown())?;
+        // Start a new thread if there are more jobs than the idle threads,
+        // otherwise or if the pool is full the job waits for a thread.
+        if self.tx.len() <= self.idle.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        let grow = self
+            .threads
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                (n < self.max_threads).then(|| n + 1)
+            })
+            .is_ok();
+        if !grow {
+            return Ok(());
+        }
+        let rx = self.rx.clone();
+        let idle = self.idle.clone();
+        let spawned = std::thread::Builder::new()
+            .name("norpc-blocking".to_owned())
+            .spawn(move || loop {
+                idle.fetch_add(1, Ordering::SeqCst);
+                let job = rx.recv();
+                idle.fetch_sub(1, Ordering::SeqCst);
+                match job {
+                    // A panic in a job doesn't kill the thread.
+                    Ok(job) => std::panic::catch_unwind(AssertUnwindSafe(job)).ok(),
+                    Err(_) => break,
+                };
+            });
+        if spawned.is_err() && self.threads.fetch_sub(1, Ordering::SeqCst) == 1 {
+            // No thread to run the job which is left in the queue.
+            return Err(SpawnError::shutdown());
+        }
+        Ok(())
+    }
+}
+
+/// Thread pool the handlers of a `#[norpc::service(blocking)]` service run on.
+///
+/// The pool is a part of the generated service so the handlers never block
+/// the executor however the service is served.
+/// The default pool is a [`ThreadPool`] with as many threads as the available parallelism.
+///
+/// ```ignore
+/// let svc = HashService::with_pool(HashApp, Pool::new(TokioExecutor));
+/// ```
+#[derive(Clone)]
+pub struct Pool {
+    spawner: Arc<dyn BlockingSpawn + Send + Sync>,
+}
+impl Pool {
+    /// Run the jobs by `spawner`, e.g. on the blocking pool of an executor.
+    pub fn new(spawner: impl BlockingSpawn + Send + Sync + 'static) -> Self {
+        Self {
+            spawner: Arc::new(spawner),
+        }
+    }
+}
+impl Default for Pool {
+    fn default() -> Self {
+        let n = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Self::new(ThreadPool::new(n))
+    }
+}
+
+/// Run a blocking job of a handler on the pool.
+///
+/// Fails if the job can't be spawned. A panic in the job is resumed in the caller.
+#[doc(hidden)]
+pub async fn run<T: Send + 'static>(
+    pool: &Pool,
+    f: impl FnOnce() -> T + Send + 'static,
+) -> Result<T, SpawnError> {
+    let (tx, rx) = oneshot::channel();
+    pool.spawner.spawn_blocking_obj(Box::new(move || {
+        tx.send(std::panic::catch_unwind(AssertUnwindSafe(f))).ok();
+    }))?;
+    match rx.await {
+        Ok(Ok(x)) => Ok(x),
+        Ok(Err(panic)) => std::panic::resume_unwind(panic),
+        // The job is dropped without being run, e.g. the executor is shutting down.
+        Err(_) => Err(SpawnError::shutdown()),
+    }
+}
@@ -0,0 +1,163 @@
+use crate::json::{Error, JsonRequest, Value};
+use futures::future::BoxFuture;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+
+type Handler = Box<
+    dyn Fn(Option<String>, String, Value) -> BoxFuture<'static, Result<Value, Error>> + Send + Sync,
+>;
+
+/// Header to send the fingerprint of the service the client was built against.
+pub const FINGERPRINT_HEADER: &str = "norpc-fingerprint";
+
+/// HTTP server to call services with JSON.
+///
+/// `POST /<Service>/<method>` with a JSON object of the arguments in the body
+/// calls the method and returns the response as JSON.
+/// The service must be declared with `#[norpc::service(json)]`.
+///
+/// This is meant for debugging, e.g. calling a service with curl.
+///
+/// A client can send the `Schema::FINGERPRINT` of its service definition
+/// in hex in the `norpc-fingerprint` header.
+/// The call fails with 409 Conflict if it differs from the one of the mounted service.
+///
+/// ```ignore
+/// let gateway = Gateway::new().mount(KVStoreService::new(app));
+/// let listener = std::net::TcpListener::bind("127.0.0.1:8080")?;
+/// tokio::spawn(gateway.serve(listener));
+/// // curl -X POST localhost:8080/KVStore/read -d '{"id": 1}'
+/// ```
+pub struct Gateway {
+    services: HashMap<&'static str, Handler>,
+}
+impl Default for Gateway {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Gateway {
+    pub fn new() -> Self {
+        Self {
+            services: HashMap::new(),
+        }
+    }
+    /// Mount a service under its name.
+    /// A service can be the generated `{Svc}Service` or a `Channel` to a running server.
+    ///
+    /// Panics if a service of the same name is already mounted.
+    pub fn mount<X, Svc>(mut self, svc: Svc) -> Self
+    where
+        X: JsonRequest + Send + 'static,
+        Svc: crate::Service<X, Response = X::Response> + Clone + Send + Sync + 'static,
+        Svc::Future: Send,
+        Svc::Error: std::fmt::Debug,
+    {
+        let handler = move |fingerprint: Option<String>, method: String, params: Value| {
+            let mut svc = svc.clone();
+            let fut: BoxFuture<'static, Result<Value, Error>> = Box::pin(async move {
+                if let Some(fingerprint) = fingerprint {
+                    crate::json::check_fingerprint::<X>(&fingerprint)?;
+                }
+                let req = X::from_json(&method, params)?;
+                crate::poll_fn(|ctx| svc.poll_ready(ctx))
+                    .await
+                    .map_err(|e| Error::Internal(format!("{:?}", e)))?;
+                let rep = svc
+                    .call(req)
+                    .await
+                    .map_err(|e| Error::Internal(format!("{:?}", e)))?;
+                X::response_to_json(rep)
+            });
+            fut
+        };
+        if self.services.insert(X::NAME, Box::new(handler)).is_some() {
+            panic!("{} is already mounted", X::NAME);
+        }
+        self
+    }
+    /// Serve HTTP requests from the listener.
+    /// This must be run in a tokio runtime.
+    pub async fn serve(self, listener: std::net::TcpListener) -> anyhow::Result<()> {
+        let gateway = Arc::new(self);
+        let make_svc = make_service_fn(move |_| {
+            let gateway = gateway.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let gateway = gateway.clone();
+                    async move { Ok::<_, Infallible>(gateway.handle(req).await) }
+                }))
+            }
+        });
+        hyper::Server::from_tcp(listener)?.serve(make_svc).await?;
+        Ok(())
+    }
+    async fn handle(&self, req: Request<Body>) -> Response<Body> {
+        if req.method() != Method::POST {
+            return error_response(StatusCode::METHOD_NOT_ALLOWED, "only POST is allowed");
+        }
+        let path: Vec<String> = req
+            .uri()
+            .path()
+            .trim_matches('/')
+            .split('/')
+            .map(|x| x.to_owned())
+            .collect();
+        let (svc_name, method) = match path.as_slice() {
+            [svc_name, method] => (svc_name, method),
+            _ => return error_response(StatusCode::NOT_FOUND, "path must be /<Service>/<method>"),
+        };
+        let handler = match self.services.get(svc_name.as_str()) {
+            Some(handler) => handler,
+            None => {
+                return error_response(
+                    StatusCode::NOT_FOUND,
+                    &format!("unknown service {}", svc_name),
+                )
+            }
+        };
+        let fingerprint = match req.headers().get(FINGERPRINT_HEADER).map(|x| x.to_str()) {
+            Some(Ok(x)) => Some(x.to_owned()),
+            Some(Err(e)) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+            None => None,
+        };
+        let body = match hyper::body::to_bytes(req.into_body()).await {
+            Ok(body) => body,
+            Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+        };
+        let params = if body.is_empty() {
+            Value::Null
+        } else {
+            match serde_json::from_slice(&body) {
+                Ok(params) => params,
+                Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+            }
+        };
+        match handler(fingerprint, method.clone(), params).await {
+            Ok(rep) => json_response(StatusCode::OK, &rep),
+            Err(e) => {
+                let status = match e {
+                    Error::UnknownMethod(_) => StatusCode::NOT_FOUND,
+                    Error::SchemaMismatch(_) => StatusCode::CONFLICT,
+                    Error::InvalidParams(_) => StatusCode::BAD_REQUEST,
+                    Error::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                };
+                error_response(status, &e.to_string())
+            }
+        }
+    }
+}
+
+fn json_response(status: StatusCode, v: &Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(Body::from(v.to_string()))
+        .unwrap()
+}
+fn error_response(status: StatusCode, msg: &str) -> Response<Body> {
+    json_response(status, &serde_json::json!({ "error": msg }))
+}
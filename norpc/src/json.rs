@@ -0,0 +1,87 @@
+use crate::{Schema, SchemaMismatch, UnknownMethod};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+pub use serde_json::Value;
+
+/// JSON encoding of a request and its response, implemented by the generated `{Svc}Request`
+/// when the service is declared with `#[norpc::service(json)]`.
+///
+/// The parameters of a method are either a JSON object keyed by the argument names
+/// or a JSON array in the order of the arguments.
+/// A missing argument is read as `null` so an `Option` argument can be omitted.
+pub trait JsonRequest: Schema + Sized {
+    type Response;
+    /// Build a request from a method name and its parameters.
+    fn from_json(method: &str, params: Value) -> Result<Self, Error>;
+    /// Encode the response to JSON.
+    fn response_to_json(rep: Self::Response) -> Result<Value, Error>;
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// The service doesn't have the method.
+    UnknownMethod(UnknownMethod),
+    /// The peer was built against a different definition of the service.
+    SchemaMismatch(SchemaMismatch),
+    /// The parameters can't be decoded into the arguments.
+    InvalidParams(String),
+    /// The service failed to process the request or the response can't be encoded.
+    Internal(String),
+}
+impl Error {
+    #[doc(hidden)]
+    pub fn unknown_method<X: Schema>(method: &str) -> Self {
+        Error::UnknownMethod(UnknownMethod {
+            service: X::NAME,
+            method: method.to_owned(),
+        })
+    }
+}
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::UnknownMethod(e) => e.fmt(f),
+            Error::SchemaMismatch(e) => e.fmt(f),
+            Error::InvalidParams(msg) => write!(f, "invalid params: {}", msg),
+            Error::Internal(msg) => write!(f, "internal error: {}", msg),
+        }
+    }
+}
+impl std::error::Error for Error {}
+
+#[doc(hidden)]
+pub struct Params {
+    inner: Value,
+}
+impl Params {
+    pub fn new(params: Value) -> Result<Self, Error> {
+        match params {
+            Value::Object(_) | Value::Array(_) | Value::Null => Ok(Self { inner: params }),
+            _ => Err(Error::InvalidParams(
+                "params must be an object or an array".to_owned(),
+            )),
+        }
+    }
+    pub fn take<T: DeserializeOwned>(&mut self, i: usize, name: &str) -> Result<T, Error> {
+        let v = match &mut self.inner {
+            Value::Object(m) => m.remove(name),
+            Value::Array(xs) => xs.get_mut(i).map(Value::take),
+            _ => None,
+        };
+        serde_json::from_value(v.unwrap_or(Value::Null))
+            .map_err(|e| Error::InvalidParams(format!("{}: {}", name, e)))
+    }
+}
+
+/// Check a fingerprint sent by a peer as 16 hex digits.
+pub(crate) fn check_fingerprint<X: Schema>(fingerprint: &str) -> Result<(), Error> {
+    let fingerprint = u64::from_str_radix(fingerprint, 16)
+        .map_err(|e| Error::InvalidParams(format!("fingerprint: {}", e)))?;
+    X::check_fingerprint(fingerprint).map_err(Error::SchemaMismatch)
+}
+
+#[doc(hidden)]
+pub fn to_value<T: Serialize>(v: T) -> Result<Value, Error> {
+    serde_json::to_value(v).map_err(|e| Error::Internal(e.to_string()))
+}
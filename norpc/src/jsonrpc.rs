@@ -0,0 +1,278 @@
+use crate::json::{check_fingerprint, Error, JsonRequest, Params, Value};
+use crate::Schema;
+use futures::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt};
+use futures::io::{AsyncWrite, AsyncWriteExt, BufReader};
+use futures::stream::FuturesUnordered;
+use futures::StreamExt;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+const SCHEMA_MISMATCH: i64 = -32000;
+
+const HANDSHAKE: &str = "rpc.handshake";
+
+/// How the messages are delimited in the stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Framing {
+    /// One message per line.
+    Lines,
+    /// `Content-Length` header before each message as in the Language Server Protocol,
+    /// which is what editors speak.
+    ContentLength,
+}
+
+/// Serve JSON-RPC 2.0 requests read from `reader` and write the responses to `writer`.
+///
+/// Messages are delimited by newlines. Use [`serve_framed`] for other framings.
+/// The method of a request is the name of the method in the service trait
+/// and the params are either named or positional.
+/// Batches and notifications are supported.
+/// Requests are processed concurrently so the responses may come out of order.
+///
+/// A client can call `rpc.handshake` with the `Schema::FINGERPRINT` of its service definition
+/// in hex as the `fingerprint` param. It answers the name and the fingerprint of the service,
+/// or fails with code -32000 if the fingerprints differ.
+///
+/// The service must be declared with `#[norpc::service(json)]`.
+/// This returns when `reader` reaches EOF and all the pending requests are answered.
+/// The returned future is `Send` if the service, its futures and the reader and writer are.
+///
+/// `reader` and `writer` are of the `futures::io` traits.
+/// Wrap tokio ones with `tokio_util::compat` (the `compat` feature of tokio-util),
+/// e.g. `tokio::io::stdin().compat()`.
+pub async fn serve<X, Svc>(
+    svc: Svc,
+    reader: impl AsyncRead + Unpin,
+    writer: impl AsyncWrite + Unpin,
+) -> std::io::Result<()>
+where
+    X: JsonRequest,
+    Svc: crate::Service<X, Response = X::Response>,
+    Svc::Error: std::fmt::Debug,
+{
+    serve_framed(svc, Framing::Lines, reader, writer).await
+}
+
+/// [`serve`] with the messages delimited by `framing`.
+/// The responses are framed in the same way.
+pub async fn serve_framed<X, Svc>(
+    svc: Svc,
+    framing: Framing,
+    reader: impl AsyncRead + Unpin,
+    mut writer: impl AsyncWrite + Unpin,
+) -> std::io::Result<()>
+where
+    X: JsonRequest,
+    Svc: crate::Service<X, Response = X::Response>,
+    Svc::Error: std::fmt::Debug,
+{
+    // Shared by the requests in `processings` which wait for the service to be ready.
+    let svc = Arc::new(Mutex::new(svc));
+    let messages = futures::stream::unfold(BufReader::new(reader), |mut reader| async move {
+        let msg = read_message(&mut reader, framing).await.transpose()?;
+        Some((msg, reader))
+    })
+    .fuse();
+    futures::pin_mut!(messages);
+    let mut processings = FuturesUnordered::new();
+    loop {
+        futures::select! {
+            msg = messages.next() => match msg {
+                // Waiting for the service to be ready is also in the future
+                // so that the requests in flight progress meanwhile.
+                Some(msg) => processings.push(handle_message(svc.clone(), msg?)),
+                None => break,
+            },
+            rep = processings.select_next_some() => {
+                write_message(&mut writer, framing, rep).await?;
+            }
+        }
+    }
+    while let Some(rep) = processings.next().await {
+        write_message(&mut writer, framing, rep).await?;
+    }
+    Ok(())
+}
+
+/// Read the next message or `None` at EOF.
+async fn read_message(
+    reader: &mut (impl AsyncBufRead + Unpin),
+    framing: Framing,
+) -> std::io::Result<Option<String>> {
+    let invalid_data = |msg: String| std::io::Error::new(std::io::ErrorKind::InvalidData, msg);
+    let mut line = String::new();
+    match framing {
+        Framing::Lines => loop {
+            line.clear();
+            if reader.read_line(&mut line).await? == 0 {
+                return Ok(None);
+            }
+            if !line.trim().is_empty() {
+                return Ok(Some(line));
+            }
+        },
+        Framing::ContentLength => {
+            let mut len = None;
+            loop {
+                line.clear();
+                if reader.read_line(&mut line).await? == 0 {
+                    return Ok(None);
+                }
+                let header = line.trim_end();
+                if header.is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.trim().eq_ignore_ascii_case("content-length") {
+                        let n = value
+                            .trim()
+                            .parse()
+                            .map_err(|e| invalid_data(format!("invalid Content-Length: {}", e)))?;
+                        len = Some(n);
+                    }
+                }
+            }
+            let len = len.ok_or_else(|| invalid_data("missing Content-Length".to_owned()))?;
+            let mut buf = vec![0; len];
+            reader.read_exact(&mut buf).await?;
+            let msg = String::from_utf8(buf).map_err(|e| invalid_data(e.to_string()))?;
+            Ok(Some(msg))
+        }
+    }
+}
+
+async fn write_message(
+    writer: &mut (impl AsyncWrite + Unpin),
+    framing: Framing,
+    rep: Option<Value>,
+) -> std::io::Result<()> {
+    if let Some(rep) = rep {
+        let body = rep.to_string();
+        let buf = match framing {
+            Framing::Lines => format!("{}\n", body),
+            Framing::ContentLength => format!("Content-Length: {}\r\n\r\n{}", body.len(), body),
+        };
+        writer.write_all(buf.as_bytes()).await?;
+        writer.flush().await?;
+    }
+    Ok(())
+}
+
+/// Call the service for all the requests in a message.
+/// Resolves to the response message or `None` if there is nothing to answer.
+async fn handle_message<X, Svc>(svc: Arc<Mutex<Svc>>, msg: String) -> Option<Value>
+where
+    X: JsonRequest,
+    Svc: crate::Service<X, Response = X::Response>,
+    Svc::Error: std::fmt::Debug,
+{
+    let msg: Value = match serde_json::from_str(&msg) {
+        Ok(msg) => msg,
+        Err(e) => return Some(error_response(Value::Null, PARSE_ERROR, &e.to_string())),
+    };
+    match msg {
+        Value::Array(reqs) if !reqs.is_empty() => {
+            let futs = reqs.into_iter().map(|req| handle_request(svc.clone(), req));
+            let reps: Vec<Value> = futures::future::join_all(futs)
+                .await
+                .into_iter()
+                .flatten()
+                .collect();
+            if reps.is_empty() {
+                None
+            } else {
+                Some(Value::Array(reps))
+            }
+        }
+        req => handle_request(svc, req).await,
+    }
+}
+
+async fn handle_request<X, Svc>(svc: Arc<Mutex<Svc>>, req: Value) -> Option<Value>
+where
+    X: JsonRequest,
+    Svc: crate::Service<X, Response = X::Response>,
+    Svc::Error: std::fmt::Debug,
+{
+    let mut req = match req {
+        Value::Object(req) => req,
+        _ => {
+            return Some(error_response(
+                Value::Null,
+                INVALID_REQUEST,
+                "invalid request",
+            ))
+        }
+    };
+    // A request without an id is a notification which is not answered.
+    let id = req.remove("id");
+    if req.get("jsonrpc") != Some(&json!("2.0")) {
+        let id = id.unwrap_or(Value::Null);
+        return Some(error_response(id, INVALID_REQUEST, "invalid request"));
+    }
+    let respond = move |result: Result<Value, (i64, String)>| {
+        let id = id?;
+        Some(match result {
+            Ok(v) => json!({ "jsonrpc": "2.0", "result": v, "id": id }),
+            Err((code, msg)) => error_response(id, code, &msg),
+        })
+    };
+    let method = match req.remove("method") {
+        Some(Value::String(method)) => method,
+        _ => return respond(Err((INVALID_REQUEST, "invalid request".to_owned()))),
+    };
+    let params = req.remove("params").unwrap_or(Value::Null);
+    if method == HANDSHAKE {
+        return respond(handshake::<X>(params).map_err(error_code));
+    }
+    let x = match X::from_json(&method, params) {
+        Ok(x) => x,
+        Err(e) => return respond(Err(error_code(e))),
+    };
+    // The service is locked only within a poll so other requests can use it.
+    let ready = crate::poll_fn(|ctx| svc.lock().unwrap().poll_ready(ctx)).await;
+    if let Err(e) = ready {
+        return respond(Err((INTERNAL_ERROR, format!("{:?}", e))));
+    }
+    // Called right after it is ready, without yielding in between.
+    let fut = svc.lock().unwrap().call(x);
+    let result = match fut.await {
+        Ok(rep) => X::response_to_json(rep).map_err(error_code),
+        Err(e) => Err((INTERNAL_ERROR, format!("{:?}", e))),
+    };
+    respond(result)
+}
+
+fn handshake<X: Schema>(params: Value) -> Result<Value, Error> {
+    let fingerprint: Option<String> = Params::new(params)?.take(0, "fingerprint")?;
+    if let Some(fingerprint) = fingerprint {
+        check_fingerprint::<X>(&fingerprint)?;
+    }
+    Ok(json!({
+        "service": X::NAME,
+        "fingerprint": format!("{:016x}", X::FINGERPRINT),
+    }))
+}
+
+fn error_code(e: Error) -> (i64, String) {
+    let code = match e {
+        Error::UnknownMethod(_) => METHOD_NOT_FOUND,
+        Error::SchemaMismatch(_) => SCHEMA_MISMATCH,
+        Error::InvalidParams(_) => INVALID_PARAMS,
+        Error::Internal(_) => INTERNAL_ERROR,
+    };
+    (code, e.to_string())
+}
+
+fn error_response(id: Value, code: i64, msg: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "error": { "code": code, "message": msg },
+        "id": id,
+    })
+}
@@ -30,13 +30,201 @@
 pub use async_trait::async_trait;
 #[doc(hidden)]
 pub use futures::future::poll_fn;
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub use serde;
 #[doc(hidden)]
 pub use tower_service::Service;
 
 /// Macro for code-generation.
 pub use norpc_macros::service;
 
+/// Static description of a service, implemented by the generated `{Svc}Request`.
+///
+/// Every method has an id given by `#[id = N]`.
+/// Once a method has one, all the methods must have one so that
+/// inserting a method never renumbers the others.
+/// Without any `#[id = N]`, the id is the position in the trait.
+/// To keep a service compatible with peers built against an older definition,
+/// fix the ids with `#[id = N]`, only add new methods with new ids and
+/// mark retired ones `#[deprecated]` instead of removing or renumbering them.
+///
+/// ```compile_fail
+/// #[norpc::service]
+/// trait Account {
+///     #[id = 1]
+///     fn balance(user: u64) -> u64;
+///     // error: `#[id = N]` is required on every method once any method has it
+///     fn deposit(user: u64, amount: u64);
+/// }
+/// ```
+pub trait Schema {
+    /// Name of the service trait.
+    const NAME: &'static str;
+    /// Fingerprint of the service definition.
+    ///
+    /// It is computed from the method ids and names and the argument and return types
+    /// and is stable across builds, so two peers can compare it to detect
+    /// that they were built against different versions of the service.
+    /// The HTTP gateway and the JSON-RPC adapter check the one a client sends
+    /// and fail the call with [`SchemaMismatch`].
+    const FINGERPRINT: u64;
+    /// Pairs of id and name of all methods.
+    const METHODS: &'static [(u32, &'static str)];
+
+    /// Id of the method this request calls.
+    fn method_id(&self) -> u32;
+    /// Name of the method this request calls.
+    fn method_name(&self) -> &'static str;
+
+    /// Find the name of a method by its id.
+    ///
+    /// No transport in this crate calls methods by id.
+    /// This is for a decoder which needs to reject an unknown id before decoding the arguments.
+    fn lookup_method(id: u32) -> Result<&'static str, UnknownMethod> {
+        Self::METHODS
+            .iter()
+            .find(|(x, _)| *x == id)
+            .map(|(_, name)| *name)
+            .ok_or_else(|| UnknownMethod {
+                service: Self::NAME,
+                method: format!("#{}", id),
+            })
+    }
+    /// Check the fingerprint a peer was built against.
+    fn check_fingerprint(fingerprint: u64) -> Result<(), SchemaMismatch> {
+        if fingerprint == Self::FINGERPRINT {
+            Ok(())
+        } else {
+            Err(SchemaMismatch {
+                service: Self::NAME,
+                expected: Self::FINGERPRINT,
+                actual: fingerprint,
+            })
+        }
+    }
+}
+
+/// Hooks called by the server when it starts and stops serving.
+///
+/// Use them for async setup and cleanup like opening and flushing connection pools.
+/// The generated `{Svc}Service` implements this trait if the app does.
+#[async_trait::async_trait]
+pub trait Lifecycle {
+    /// Called before the server accepts the first request.
+    async fn on_start(&self) {}
+    /// Called after the last channel is dropped or shutdown is requested.
+    async fn on_stop(&self) {}
+}
+
+/// Service which can be called synchronously, blocking the current thread.
+///
+/// The generated `{Svc}BlockingClient` sends requests through this trait
+/// so the callers needn't be in an async runtime.
+/// Every service implements it, e.g. a channel or the service itself.
+pub trait BlockingCall<X> {
+    type Response;
+    type Error;
+    fn call_blocking(&mut self, req: X) -> Result<Self::Response, Self::Error>;
+}
+
+/// Blocks the current thread until the service is ready and the response arrives.
+/// Don't call this in an async context because it blocks the executor.
+impl<X, S: tower_service::Service<X>> BlockingCall<X> for S {
+    type Response = S::Response;
+    type Error = S::Error;
+
+    fn call_blocking(&mut self, req: X) -> Result<Self::Response, Self::Error> {
+        futures::executor::block_on(async move {
+            futures::future::poll_fn(|ctx| self.poll_ready(ctx)).await?;
+            self.call(req).await
+        })
+    }
+}
+
+/// Error for a request to a method the service doesn't have.
+///
+/// The in-process server can't get one because its requests are typed.
+/// The JSON adapters answer it for a method name the service doesn't know.
+#[derive(Debug)]
+pub struct UnknownMethod {
+    pub service: &'static str,
+    pub method: String,
+}
+impl std::fmt::Display for UnknownMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown method {}/{}", self.service, self.method)
+    }
+}
+impl std::error::Error for UnknownMethod {}
+
+/// Error for a peer built against a different definition of the service.
+#[derive(Debug)]
+pub struct SchemaMismatch {
+    pub service: &'static str,
+    /// Fingerprint of this side.
+    pub expected: u64,
+    /// Fingerprint the peer sent.
+    pub actual: u64,
+}
+impl std::fmt::Display for SchemaMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "schema mismatch of {}: expected {:016x} but got {:016x}",
+            self.service, self.expected, self.actual
+        )
+    }
+}
+impl std::error::Error for SchemaMismatch {}
+
+mod registry;
+pub use registry::*;
+
+#[cfg(feature = "shard")]
+#[cfg_attr(docsrs, doc(cfg(feature = "shard")))]
+/// Partitioning of requests by key.
+pub mod shard;
+
+#[doc(hidden)]
+pub mod actor;
+/// Thread pools for the handlers of blocking services.
+pub mod blocking;
+
 #[cfg(feature = "runtime")]
 #[cfg_attr(docsrs, doc(cfg(feature = "runtime")))]
 /// Runtime implementation.
 pub mod runtime;
+
+#[cfg(feature = "testing")]
+#[cfg_attr(docsrs, doc(cfg(feature = "testing")))]
+/// Utilities for testing services.
+pub mod testing;
+
+#[cfg(feature = "sim")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sim")))]
+/// Deterministic simulation.
+pub mod sim;
+
+#[cfg(any(feature = "runtime", feature = "testing", feature = "sim"))]
+mod rng;
+
+#[cfg(feature = "json")]
+#[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+/// JSON encoding of requests and responses.
+pub mod json;
+
+#[cfg(feature = "jsonrpc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "jsonrpc")))]
+/// JSON-RPC 2.0 adapter to services.
+pub mod jsonrpc;
+
+#[cfg(feature = "record")]
+#[cfg_attr(docsrs, doc(cfg(feature = "record")))]
+/// Record and replay of requests and responses.
+pub mod record;
+
+#[cfg(feature = "http-gateway")]
+#[cfg_attr(docsrs, doc(cfg(feature = "http-gateway")))]
+/// HTTP gateway to services.
+pub mod http;
@@ -0,0 +1,234 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// A recorded pair of request and response.
+/// The recording is a file of these entries in JSON, one per line.
+#[derive(Serialize, Deserialize)]
+pub struct Entry<X, Y> {
+    /// When the request was made in microseconds since the UNIX epoch.
+    pub time: u64,
+    /// How long it took to get the response in microseconds.
+    pub elapsed: u64,
+    pub request: X,
+    pub response: Y,
+}
+
+type Writer = Arc<Mutex<Box<dyn Write + Send>>>;
+type OnError = Arc<dyn Fn(RecordError) + Send + Sync>;
+
+/// Error in recording an entry. The call itself succeeds anyway.
+#[derive(Debug)]
+pub enum RecordError {
+    /// The request or the response can't be serialized so the entry is not recorded.
+    Serialize(serde_json::Error),
+    /// The entry can't be written.
+    Write(std::io::Error),
+}
+impl std::fmt::Display for RecordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordError::Serialize(e) => write!(f, "failed to serialize an entry: {}", e),
+            RecordError::Write(e) => write!(f, "failed to write an entry: {}", e),
+        }
+    }
+}
+impl std::error::Error for RecordError {}
+
+/// Layer to record the requests and responses passing through a service.
+///
+/// This can wrap either a `Channel` in the client side or a service in the server side.
+/// The requests and responses must be serializable,
+/// which is the case for the service declared with `#[norpc::service(serde)]`.
+/// Failed requests are not recorded.
+/// An entry which can't be serialized or written is reported to the error handler,
+/// which prints it to stderr by default.
+#[derive(Clone)]
+pub struct RecordLayer {
+    writer: Writer,
+    on_error: OnError,
+}
+impl RecordLayer {
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Arc::new(Mutex::new(Box::new(writer))),
+            on_error: Arc::new(|e| eprintln!("norpc: {}", e)),
+        }
+    }
+    /// Record to a new file at `path`.
+    pub fn create(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let f = std::fs::File::create(path)?;
+        Ok(Self::new(f))
+    }
+    /// Set the handler of the errors in recording.
+    pub fn on_error(mut self, f: impl Fn(RecordError) + Send + Sync + 'static) -> Self {
+        self.on_error = Arc::new(f);
+        self
+    }
+}
+impl<S> tower_layer::Layer<S> for RecordLayer {
+    type Service = Record<S>;
+    fn layer(&self, inner: S) -> Self::Service {
+        Record {
+            inner,
+            writer: self.writer.clone(),
+            on_error: self.on_error.clone(),
+        }
+    }
+}
+
+/// Service made by `RecordLayer`.
+#[derive(Clone)]
+pub struct Record<S> {
+    inner: S,
+    writer: Writer,
+    on_error: OnError,
+}
+impl<X, S> crate::Service<X> for Record<S>
+where
+    X: Serialize,
+    S: crate::Service<X>,
+    S::Response: Serialize + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = std::pin::Pin<
+        Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>,
+    >;
+
+    fn poll_ready(
+        &mut self,
+        ctx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(ctx)
+    }
+
+    fn call(&mut self, req: X) -> Self::Future {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        let start = Instant::now();
+        let request = serde_json::to_value(&req);
+        let writer = self.writer.clone();
+        let on_error = self.on_error.clone();
+        let fut = self.inner.call(req);
+        Box::pin(async move {
+            let rep = fut.await?;
+            let line = request.and_then(|request| {
+                let entry = Entry {
+                    time,
+                    elapsed: start.elapsed().as_micros() as u64,
+                    request,
+                    response: &rep,
+                };
+                serde_json::to_vec(&entry)
+            });
+            let written = line.map_err(RecordError::Serialize).and_then(|mut line| {
+                line.push(b'\n');
+                writer
+                    .lock()
+                    .unwrap()
+                    .write_all(&line)
+                    .map_err(RecordError::Write)
+            });
+            if let Err(e) = written {
+                on_error(e);
+            }
+            Ok(rep)
+        })
+    }
+}
+
+/// Error for a request which has no response left in the recording.
+#[derive(Debug)]
+pub struct ReplayMiss {
+    /// The request in JSON.
+    pub request: String,
+}
+impl std::fmt::Display for ReplayMiss {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no recorded response for {}", self.request)
+    }
+}
+impl std::error::Error for ReplayMiss {}
+
+/// Service that answers the requests with the responses in a recording.
+///
+/// A request is answered with the response recorded for the same request.
+/// If the same request is recorded many times, the responses are returned
+/// in the recorded order.
+/// Build a server with this service to replay the recording to a client.
+///
+/// A request without a recorded response fails with [`ReplayMiss`].
+/// Note that the generated client panics on any error
+/// so call this service by `tower::ServiceExt::oneshot` to handle the misses.
+pub struct Replay<X, Y> {
+    responses: Arc<Mutex<HashMap<String, VecDeque<Y>>>>,
+    phantom_x: std::marker::PhantomData<fn(X)>,
+}
+impl<X, Y> Clone for Replay<X, Y> {
+    fn clone(&self) -> Self {
+        Self {
+            responses: self.responses.clone(),
+            phantom_x: std::marker::PhantomData,
+        }
+    }
+}
+impl<X, Y: DeserializeOwned> Replay<X, Y> {
+    pub fn new(reader: impl BufRead) -> anyhow::Result<Self> {
+        let mut responses: HashMap<String, VecDeque<Y>> = HashMap::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: Entry<Value, Y> = serde_json::from_str(&line)?;
+            responses
+                .entry(entry.request.to_string())
+                .or_default()
+                .push_back(entry.response);
+        }
+        Ok(Self {
+            responses: Arc::new(Mutex::new(responses)),
+            phantom_x: std::marker::PhantomData,
+        })
+    }
+    /// Load the recording at `path`.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let f = std::fs::File::open(path)?;
+        Self::new(std::io::BufReader::new(f))
+    }
+}
+impl<X: Serialize, Y: Send + 'static> crate::Service<X> for Replay<X, Y> {
+    type Response = Y;
+    type Error = ReplayMiss;
+    type Future = futures::future::Ready<Result<Y, ReplayMiss>>;
+
+    fn poll_ready(
+        &mut self,
+        _: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        Ok(()).into()
+    }
+
+    fn call(&mut self, req: X) -> Self::Future {
+        // A request which can't be serialized can't be in the recording either.
+        let key = serde_json::to_value(&req)
+            .map(|v| v.to_string())
+            .unwrap_or_default();
+        let rep = self
+            .responses
+            .lock()
+            .unwrap()
+            .get_mut(&key)
+            .and_then(|q| q.pop_front());
+        futures::future::ready(rep.ok_or(ReplayMiss { request: key }))
+    }
+}
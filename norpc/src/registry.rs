@@ -0,0 +1,123 @@
+use futures::channel::oneshot;
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Error of a [`Registry`] operation.
+#[derive(Debug)]
+pub enum RegistryError {
+    /// Nothing is registered under the name.
+    NotFound(String),
+    /// Something is registered under the name but it is of another type.
+    TypeMismatch(String),
+    /// The name is already taken.
+    AlreadyRegistered(String),
+}
+impl std::fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistryError::NotFound(name) => write!(f, "{} is not registered", name),
+            RegistryError::TypeMismatch(name) => {
+                write!(f, "{} is registered as another type", name)
+            }
+            RegistryError::AlreadyRegistered(name) => write!(f, "{} is already registered", name),
+        }
+    }
+}
+impl std::error::Error for RegistryError {}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<String, Box<dyn Any + Send>>,
+    waiters: HashMap<String, Vec<oneshot::Sender<()>>>,
+}
+
+/// Registry of clients (or channels) by name.
+///
+/// Components look up the clients by name and type at runtime
+/// instead of taking all of them in their constructors.
+/// Because a client can be looked up after it is registered with [`Registry::wait`],
+/// services can depend on each other in cycles.
+///
+/// ```ignore
+/// let registry = Registry::new();
+/// registry.register("id-store", IdStoreClient::new(chan))?;
+/// let cli: IdStoreClient<Channel<IdStoreRequest, IdStoreResponse>> = registry.get("id-store")?;
+/// ```
+#[derive(Clone, Default)]
+pub struct Registry {
+    inner: Arc<Mutex<Inner>>,
+}
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Register `x` under `name` and wake the tasks waiting for it.
+    pub fn register<T: Clone + Send + 'static>(
+        &self,
+        name: impl Into<String>,
+        x: T,
+    ) -> Result<(), RegistryError> {
+        let name = name.into();
+        let mut inner = self.inner.lock().unwrap();
+        if inner.entries.contains_key(&name) {
+            return Err(RegistryError::AlreadyRegistered(name));
+        }
+        inner.entries.insert(name.clone(), Box::new(x));
+        for waiter in inner.waiters.remove(&name).unwrap_or_default() {
+            waiter.send(()).ok();
+        }
+        Ok(())
+    }
+    /// Remove the entry under `name`. Returns `false` if nothing is registered.
+    pub fn unregister(&self, name: &str) -> bool {
+        self.inner.lock().unwrap().entries.remove(name).is_some()
+    }
+    /// Get a clone of the entry under `name`.
+    pub fn get<T: Clone + 'static>(&self, name: &str) -> Result<T, RegistryError> {
+        let inner = self.inner.lock().unwrap();
+        Self::lookup(&inner, name)?.ok_or_else(|| RegistryError::NotFound(name.to_owned()))
+    }
+    /// Same as [`Registry::get`] but waits until something is registered under `name`.
+    pub async fn wait<T: Clone + 'static>(&self, name: &str) -> Result<T, RegistryError> {
+        loop {
+            let rx = {
+                let mut inner = self.inner.lock().unwrap();
+                if let Some(x) = Self::lookup(&inner, name)? {
+                    return Ok(x);
+                }
+                // Drop the waiters which gave up so they don't pile up.
+                inner.waiters.retain(|_, txs| {
+                    txs.retain(|tx| !tx.is_canceled());
+                    !txs.is_empty()
+                });
+                let (tx, rx) = oneshot::channel();
+                inner.waiters.entry(name.to_owned()).or_default().push(tx);
+                rx
+            };
+            // The sender is never dropped without sending
+            // because the registry outlives this call.
+            rx.await.ok();
+        }
+    }
+    /// Number of the waiters kept for `name`, including the ones which gave up
+    /// and are not dropped yet. For tests.
+    #[doc(hidden)]
+    pub fn waiters(&self, name: &str) -> usize {
+        let inner = self.inner.lock().unwrap();
+        inner.waiters.get(name).map_or(0, Vec::len)
+    }
+    fn lookup<T: Clone + 'static>(inner: &Inner, name: &str) -> Result<Option<T>, RegistryError> {
+        match inner.entries.get(name) {
+            None => Ok(None),
+            Some(x) => {
+                // Deref the box so that the downcast is against the entry, not the box.
+                let x: &dyn Any = &**x;
+                x.downcast_ref::<T>()
+                    .cloned()
+                    .map(Some)
+                    .ok_or_else(|| RegistryError::TypeMismatch(name.to_owned()))
+            }
+        }
+    }
+}
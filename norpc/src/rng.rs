@@ -0,0 +1,24 @@
+/// Small deterministic random number generator (SplitMix64)
+/// so that the runs with the same seed are reproducible.
+pub(crate) struct Rng {
+    state: u64,
+}
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+    /// Returns true with probability `p`.
+    #[cfg_attr(not(feature = "testing"), allow(dead_code))]
+    pub fn gen_bool(&mut self, p: f64) -> bool {
+        // 53 bits are enough to fill the mantissa of f64.
+        let x = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        x < p
+    }
+}
@@ -0,0 +1,237 @@
+use super::ServerBuilder;
+use crate::Registry;
+use futures::channel::oneshot;
+use futures::task::{Spawn, SpawnExt};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+type Executor = Arc<dyn Spawn + Send + Sync>;
+type Start = Box<dyn FnOnce(&Registry, Executor) -> Result<Running, ContainerError> + Send>;
+
+/// Error in wiring the services of a [`Container`].
+#[derive(Debug)]
+pub enum ContainerError {
+    /// Two services have the same name.
+    Duplicate(String),
+    /// A service depends on a service which isn't in the container.
+    UnknownDependency { name: String, dependency: String },
+    /// The services in the list depend on each other in a cycle.
+    Cycle(Vec<String>),
+    /// The executor failed to spawn a server.
+    Spawn(String),
+}
+impl std::fmt::Display for ContainerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ContainerError::Duplicate(name) => write!(f, "{} is declared twice", name),
+            ContainerError::UnknownDependency { name, dependency } => {
+                write!(f, "{} depends on unknown service {}", name, dependency)
+            }
+            ContainerError::Cycle(names) => {
+                write!(f, "dependency cycle among {}", names.join(", "))
+            }
+            ContainerError::Spawn(name) => write!(f, "failed to spawn {}", name),
+        }
+    }
+}
+impl std::error::Error for ContainerError {}
+
+/// A spawned server.
+struct Running {
+    name: String,
+    shutdown: Box<dyn FnOnce() + Send>,
+    stopped: oneshot::Receiver<()>,
+}
+
+struct Unit {
+    name: String,
+    dependencies: Vec<String>,
+    start: Start,
+}
+
+/// Container which wires services by their dependencies.
+///
+/// Each service is declared with the names of the services it depends on.
+/// On start, the servers are spawned in topological order so the channels to
+/// the dependencies are in the registry when a factory is called.
+/// Each channel is registered under the name of its service.
+/// On shutdown, the servers are stopped in the reverse order.
+///
+/// ```ignore
+/// let mut container = Container::new();
+/// container.service("id-alloc", &["id-store"], |registry| {
+///     let chan: Channel<IdStoreRequest, IdStoreResponse> = registry.get("id-store").unwrap();
+///     ServerBuilder::new(IdAllocService::new(IdAllocApp::new(IdStoreClient::new(chan))))
+/// });
+/// container.service("id-store", &[], |_| ServerBuilder::new(IdStoreService::new(IdStoreApp::new())));
+/// let running = container.start(TokioExecutor)?;
+/// running.shutdown().await;
+/// ```
+#[derive(Default)]
+pub struct Container {
+    registry: Registry,
+    units: Vec<Unit>,
+}
+impl Container {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Use `registry` instead of a new one.
+    pub fn with_registry(registry: Registry) -> Self {
+        Self {
+            registry,
+            units: vec![],
+        }
+    }
+    /// Declare a service named `name` which depends on `dependencies`.
+    pub fn service<X, Svc>(
+        &mut self,
+        name: impl Into<String>,
+        dependencies: &[&str],
+        factory: impl FnOnce(&Registry) -> ServerBuilder<X, Svc> + Send + 'static,
+    ) -> &mut Self
+    where
+        X: 'static + Send,
+        Svc: crate::Service<X> + 'static + Send,
+        Svc::Future: Send,
+        Svc::Response: Send,
+    {
+        let name = name.into();
+        let unit_name = name.clone();
+        let start = move |registry: &Registry, executor: Executor| {
+            let (chan, server) = factory(registry).build();
+            let handle = server.handle();
+            // Register before spawning so that a taken name leaves no server running.
+            registry
+                .register(name.clone(), chan)
+                .map_err(|_| ContainerError::Duplicate(name.clone()))?;
+            let (tx, stopped) = oneshot::channel();
+            let fut = {
+                let executor = executor.clone();
+                async move {
+                    server.serve(executor).await;
+                    tx.send(()).ok();
+                }
+            };
+            if executor.spawn(fut).is_err() {
+                registry.unregister(&name);
+                return Err(ContainerError::Spawn(name));
+            }
+            Ok(Running {
+                name,
+                shutdown: Box::new(move || handle.shutdown()),
+                stopped,
+            })
+        };
+        self.units.push(Unit {
+            name: unit_name,
+            dependencies: dependencies.iter().map(|x| x.to_string()).collect(),
+            start: Box::new(start),
+        });
+        self
+    }
+    /// Spawn all the servers in the order of the dependencies.
+    ///
+    /// Nothing is spawned if the dependencies are broken.
+    pub fn start(
+        self,
+        executor: impl Spawn + Send + Sync + 'static,
+    ) -> Result<RunningContainer, ContainerError> {
+        let order = self.sort()?;
+        let executor: Executor = Arc::new(executor);
+        let mut units: Vec<Option<Unit>> = self.units.into_iter().map(Some).collect();
+        let mut running = vec![];
+        for i in order {
+            let unit = units[i].take().unwrap();
+            match (unit.start)(&self.registry, executor.clone()) {
+                Ok(x) => running.push(x),
+                Err(e) => {
+                    // Don't leave the started ones running.
+                    for x in running.into_iter().rev() {
+                        self.registry.unregister(&x.name);
+                        (x.shutdown)();
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(RunningContainer {
+            registry: self.registry,
+            running,
+        })
+    }
+    /// Topological sort by Kahn's algorithm.
+    fn sort(&self) -> Result<Vec<usize>, ContainerError> {
+        let mut index = HashMap::new();
+        for (i, unit) in self.units.iter().enumerate() {
+            if index.insert(unit.name.as_str(), i).is_some() {
+                return Err(ContainerError::Duplicate(unit.name.clone()));
+            }
+        }
+        let mut n_deps = vec![0; self.units.len()];
+        let mut dependents = vec![vec![]; self.units.len()];
+        for (i, unit) in self.units.iter().enumerate() {
+            for dep in &unit.dependencies {
+                let j =
+                    *index
+                        .get(dep.as_str())
+                        .ok_or_else(|| ContainerError::UnknownDependency {
+                            name: unit.name.clone(),
+                            dependency: dep.clone(),
+                        })?;
+                n_deps[i] += 1;
+                dependents[j].push(i);
+            }
+        }
+        let mut queue: VecDeque<usize> =
+            (0..self.units.len()).filter(|&i| n_deps[i] == 0).collect();
+        let mut order = vec![];
+        while let Some(j) = queue.pop_front() {
+            order.push(j);
+            for &i in &dependents[j] {
+                n_deps[i] -= 1;
+                if n_deps[i] == 0 {
+                    queue.push_back(i);
+                }
+            }
+        }
+        if order.len() < self.units.len() {
+            let names = (0..self.units.len())
+                .filter(|&i| n_deps[i] > 0)
+                .map(|i| self.units[i].name.clone())
+                .collect();
+            return Err(ContainerError::Cycle(names));
+        }
+        Ok(order)
+    }
+}
+
+/// Servers spawned by a [`Container`].
+pub struct RunningContainer {
+    registry: Registry,
+    running: Vec<Running>,
+}
+impl RunningContainer {
+    /// Registry which has the channels to the services.
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+    /// Names of the services in the order they are started.
+    pub fn names(&self) -> Vec<&str> {
+        self.running.iter().map(|x| x.name.as_str()).collect()
+    }
+    /// Stop the servers in the reverse order they are started.
+    ///
+    /// A server is stopped after the servers depending on it have stopped.
+    pub async fn shutdown(self) {
+        stop(self.running, &self.registry).await;
+    }
+}
+
+async fn stop(running: Vec<Running>, registry: &Registry) {
+    for x in running.into_iter().rev() {
+        registry.unregister(&x.name);
+        (x.shutdown)();
+        x.stopped.await.ok();
+    }
+}
@@ -0,0 +1,180 @@
+use super::{process, serve_requests, CoreRequest, Restarted};
+#[cfg(feature = "tokio-executor")]
+use super::{Channel, ServerBuilder};
+use futures::channel::oneshot;
+use std::cell::Cell;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+/// Builder of a server for non-Send services.
+///
+/// The server and the channels stay on the thread they are created on
+/// so the requests, the responses and the service needn't be `Send`.
+pub struct LocalServerBuilder<X, Svc> {
+    svc: Svc,
+    phantom_x: PhantomData<X>,
+}
+impl<X: 'static, Svc: crate::Service<X> + 'static> LocalServerBuilder<X, Svc> {
+    pub fn new(svc: Svc) -> Self {
+        Self {
+            svc,
+            phantom_x: PhantomData,
+        }
+    }
+    pub fn build(self) -> (LocalChannel<X, Svc::Response>, LocalServer<X, Svc>) {
+        let (tx, rx) = flume::unbounded();
+        let server = LocalServer {
+            service: self.svc,
+            rx,
+        };
+        let chan = LocalChannel::new(tx);
+        (chan, server)
+    }
+}
+
+/// Channel to a [`LocalServer`]. This is `!Send`.
+pub struct LocalChannel<X, Y> {
+    next_id: Rc<Cell<u64>>,
+    stream_id: u64,
+    tx: flume::Sender<CoreRequest<X, Y>>,
+}
+impl<X, Y> LocalChannel<X, Y> {
+    fn new(tx: flume::Sender<CoreRequest<X, Y>>) -> Self {
+        Self {
+            stream_id: 0,
+            next_id: Rc::new(Cell::new(1)),
+            tx,
+        }
+    }
+}
+impl<X, Y> Clone for LocalChannel<X, Y> {
+    fn clone(&self) -> Self {
+        let next_id = self.next_id.clone();
+        let stream_id = next_id.get();
+        next_id.set(stream_id + 1);
+        Self {
+            stream_id,
+            next_id,
+            tx: self.tx.clone(),
+        }
+    }
+}
+impl<X, Y> Drop for LocalChannel<X, Y> {
+    fn drop(&mut self) {
+        let cancel_req = CoreRequest::Cancel {
+            stream_id: self.stream_id,
+        };
+        self.tx.send(cancel_req).ok();
+    }
+}
+impl<X: 'static, Y: 'static> crate::Service<X> for LocalChannel<X, Y> {
+    type Response = Y;
+    type Error = anyhow::Error;
+    type Future = std::pin::Pin<Box<dyn std::future::Future<Output = Result<Y, Self::Error>>>>;
+
+    fn poll_ready(
+        &mut self,
+        _: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        Ok(()).into()
+    }
+
+    fn call(&mut self, req: X) -> Self::Future {
+        let tx = self.tx.clone();
+        let stream_id = self.stream_id;
+        Box::pin(async move {
+            let (tx1, rx1) = oneshot::channel::<Result<Y, Restarted>>();
+            let req = CoreRequest::AppRequest {
+                inner: req,
+                tx: tx1,
+                stream_id,
+            };
+            if tx.send_async(req).await.is_err() {
+                anyhow::bail!("failed to send a request");
+            }
+            let rep = rx1.await??;
+            Ok(rep)
+        })
+    }
+}
+
+/// Server for non-Send services.
+///
+/// It doesn't call the `Lifecycle` hooks
+/// because they are `Send` futures which non-Send services can't make.
+pub struct LocalServer<X, Svc: crate::Service<X>> {
+    service: Svc,
+    rx: flume::Receiver<CoreRequest<X, Svc::Response>>,
+}
+impl<X: 'static, Svc: crate::Service<X> + 'static> LocalServer<X, Svc> {
+    /// Serve the requests spawning the handlers on the current thread.
+    pub async fn serve(self, executor: impl futures::task::LocalSpawn) {
+        use futures::task::LocalSpawnExt;
+        let mut service = self.service;
+        serve_requests(
+            &mut service,
+            &self.rx,
+            futures::stream::empty(),
+            |fut, tx| {
+                let (fut, abort_handle) = process(fut, tx);
+                executor.spawn_local(fut).ok().map(|_| abort_handle)
+            },
+        )
+        .await;
+    }
+}
+
+#[cfg(feature = "tokio-executor")]
+impl<X: 'static + Send, Svc: crate::Service<X> + 'static> ServerBuilder<X, Svc>
+where
+    Svc::Response: Send,
+{
+    /// Spawn a thread named `name` which runs the server on its own current-thread tokio runtime.
+    ///
+    /// The service is made by `factory` on the thread so it needn't be `Send`.
+    /// This is useful for thread-affine resources like SQLite connections.
+    /// The thread exits when all the channels are dropped.
+    /// The `Lifecycle` hooks are not called as with [`LocalServer`].
+    #[cfg_attr(docsrs, doc(cfg(feature = "tokio-executor")))]
+    #[allow(clippy::type_complexity)]
+    pub fn spawn_on_thread(
+        name: impl Into<String>,
+        factory: impl FnOnce() -> Svc + Send + 'static,
+    ) -> std::io::Result<(Channel<X, Svc::Response>, std::thread::JoinHandle<()>)> {
+        let (tx, rx) = flume::unbounded();
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let handle = std::thread::Builder::new()
+            .name(name.into())
+            .spawn(move || {
+                let server = LocalServer {
+                    service: factory(),
+                    rx,
+                };
+                let local = tokio::task::LocalSet::new();
+                local.block_on(&rt, server.serve(TokioLocalExecutor));
+            })?;
+        let chan = Channel::new(tx);
+        Ok((chan, handle))
+    }
+}
+
+#[cfg(feature = "tokio-executor")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio-executor")))]
+/// Tokio support for the local server.
+///
+/// The futures are spawned by `tokio::task::spawn_local`
+/// so the server must run in a `tokio::task::LocalSet`.
+pub struct TokioLocalExecutor;
+
+#[cfg(feature = "tokio-executor")]
+impl futures::task::LocalSpawn for TokioLocalExecutor {
+    fn spawn_local_obj(
+        &self,
+        future: futures::task::LocalFutureObj<'static, ()>,
+    ) -> Result<(), futures::task::SpawnError> {
+        tokio::task::spawn_local(future);
+        Ok(())
+    }
+}
@@ -1,24 +1,59 @@
 use std::marker::PhantomData;
 
 use futures::channel::oneshot;
+use futures::future::{AbortHandle, BoxFuture};
 use futures::StreamExt;
+use std::any::Any;
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
-enum CoreRequest<X, Y> {
-    AppRequest {
-        inner: X,
-        tx: oneshot::Sender<Y>,
-        stream_id: u64,
-    },
-    Cancel {
-        stream_id: u64,
-    },
+mod container;
+mod local;
+mod replicas;
+mod router;
+mod supervisor;
+pub use container::*;
+pub use local::*;
+use replicas::{InFlight, Replicas};
+pub use router::*;
+pub use supervisor::*;
+
+pub use crate::blocking::BlockingSpawn;
+
+/// Sender of the response. The error is sent when the service is restarted by a supervisor.
+type ReplyTx<Y> = oneshot::Sender<Result<Y, Restarted>>;
+
+/// Message to a serve loop. `tx` is what the loop passes to the handler with the request.
+enum Envelope<X, T> {
+    AppRequest { inner: X, tx: T, stream_id: u64 },
+    Cancel { stream_id: u64 },
+}
+type CoreRequest<X, Y> = Envelope<X, ReplyTx<Y>>;
+
+/// Lifecycle hooks of the service called by the server.
+struct Hooks<Svc> {
+    on_start: for<'a> fn(&'a Svc) -> BoxFuture<'a, ()>,
+    on_stop: for<'a> fn(&'a Svc) -> BoxFuture<'a, ()>,
+}
+impl<Svc: crate::Lifecycle + Sync> Hooks<Svc> {
+    fn new() -> Self {
+        fn on_start<Svc: crate::Lifecycle + Sync>(svc: &Svc) -> BoxFuture<'_, ()> {
+            svc.on_start()
+        }
+        fn on_stop<Svc: crate::Lifecycle + Sync>(svc: &Svc) -> BoxFuture<'_, ()> {
+            svc.on_stop()
+        }
+        Self {
+            on_start: on_start::<Svc>,
+            on_stop: on_stop::<Svc>,
+        }
+    }
 }
 
 pub struct ServerBuilder<X, Svc> {
     svc: Svc,
+    hooks: Option<Hooks<Svc>>,
     phantom_x: PhantomData<X>,
 }
 impl<X, Svc: crate::Service<X> + 'static + Send> ServerBuilder<X, Svc>
@@ -29,13 +64,26 @@ where
 {
     pub fn new(svc: Svc) -> Self {
         Self {
-            svc: svc,
+            svc,
+            hooks: None,
             phantom_x: PhantomData,
         }
     }
+    /// Call the `Lifecycle` hooks of the service when the server starts and stops.
+    ///
+    /// The generated `{Svc}Service` implements `Lifecycle` if the app does.
+    /// `on_stop` is called on the service the server has at the time,
+    /// which may be one given by [`ServerHandle::replace`].
+    pub fn lifecycle(mut self) -> Self
+    where
+        Svc: crate::Lifecycle + Sync,
+    {
+        self.hooks = Some(Hooks::new());
+        self
+    }
     pub fn build(self) -> (Channel<X, Svc::Response>, Server<X, Svc>) {
         let (tx, rx) = flume::unbounded();
-        let server = Server::new(rx, self.svc);
+        let server = Server::new(rx, self.svc, self.hooks);
         let chan = Channel::new(tx);
         (chan, server)
     }
@@ -44,14 +92,17 @@ where
 pub struct Channel<X, Y> {
     next_id: Arc<AtomicU64>,
     stream_id: u64,
-    tx: flume::Sender<CoreRequest<X, Y>>,
+    tx: ChannelTx<X, Y>,
 }
 impl<X, Y> Channel<X, Y> {
     fn new(tx: flume::Sender<CoreRequest<X, Y>>) -> Self {
+        Self::with_tx(ChannelTx::Direct(tx))
+    }
+    fn with_tx(tx: ChannelTx<X, Y>) -> Self {
         Self {
             stream_id: 0,
             next_id: Arc::new(AtomicU64::new(1)),
-            tx: tx,
+            tx,
         }
     }
 }
@@ -61,17 +112,14 @@ impl<X, Y> Clone for Channel<X, Y> {
         let stream_id = next_id.fetch_add(1, Ordering::SeqCst);
         Self {
             stream_id,
-            next_id: next_id,
+            next_id,
             tx: self.tx.clone(),
         }
     }
 }
 impl<X, Y> Drop for Channel<X, Y> {
     fn drop(&mut self) {
-        let cancel_req = CoreRequest::Cancel {
-            stream_id: self.stream_id,
-        };
-        self.tx.send(cancel_req).ok();
+        self.tx.send_cancel(self.stream_id);
     }
 }
 impl<X: 'static + Send, Y: 'static + Send> crate::Service<X> for Channel<X, Y> {
@@ -91,24 +139,108 @@ impl<X: 'static + Send, Y: 'static + Send> crate::Service<X> for Channel<X, Y> {
         let tx = self.tx.clone();
         let stream_id = self.stream_id;
         Box::pin(async move {
-            let (tx1, rx1) = oneshot::channel::<Y>();
-            let req = CoreRequest::AppRequest {
-                inner: req,
-                tx: tx1,
-                stream_id,
-            };
-            if tx.send_async(req).await.is_err() {
-                anyhow::bail!("failed to send a request");
-            }
-            let rep = rx1.await?;
+            let (tx1, rx1) = oneshot::channel::<Result<Y, Restarted>>();
+            let _in_flight = tx.send_request(req, tx1, stream_id)?;
+            let rep = rx1.await??;
             Ok(rep)
         })
     }
 }
 
+/// Sender of a channel to the server.
+enum ChannelTx<X, Y> {
+    Direct(flume::Sender<CoreRequest<X, Y>>),
+    /// Through the queue shared by the services of a [`Router`].
+    Routed {
+        route: usize,
+        tx: flume::Sender<RoutedRequest>,
+    },
+    /// To one of the replicas made by [`ServerBuilder::replicas`].
+    Balanced(Arc<Replicas<X, Y>>),
+}
+impl<X, Y> Clone for ChannelTx<X, Y> {
+    fn clone(&self) -> Self {
+        match self {
+            ChannelTx::Direct(tx) => ChannelTx::Direct(tx.clone()),
+            ChannelTx::Routed { route, tx } => ChannelTx::Routed {
+                route: *route,
+                tx: tx.clone(),
+            },
+            ChannelTx::Balanced(replicas) => ChannelTx::Balanced(replicas.clone()),
+        }
+    }
+}
+impl<X, Y> ChannelTx<X, Y> {
+    fn send_cancel(&self, stream_id: u64) {
+        match self {
+            ChannelTx::Direct(tx) => {
+                tx.send(CoreRequest::Cancel { stream_id }).ok();
+            }
+            ChannelTx::Routed { tx, .. } => {
+                tx.send(Envelope::Cancel { stream_id }).ok();
+            }
+            // Only to the replicas the stream has sent requests to.
+            ChannelTx::Balanced(replicas) => {
+                for i in replicas.close(stream_id) {
+                    replicas.txs[i].send(CoreRequest::Cancel { stream_id }).ok();
+                }
+            }
+        }
+    }
+}
+impl<X: 'static + Send, Y: 'static + Send> ChannelTx<X, Y> {
+    /// Send a request. The returned guard counts it in flight if it goes to a replica.
+    fn send_request(
+        &self,
+        inner: X,
+        tx: ReplyTx<Y>,
+        stream_id: u64,
+    ) -> anyhow::Result<Option<InFlight>> {
+        let mut in_flight = None;
+        // The queues are unbounded so sending never blocks.
+        let ok = match self {
+            ChannelTx::Direct(chan) => chan
+                .send(CoreRequest::AppRequest {
+                    inner,
+                    tx,
+                    stream_id,
+                })
+                .is_ok(),
+            ChannelTx::Routed { route, tx: chan } => chan
+                .send(Envelope::AppRequest {
+                    inner: RoutedCall {
+                        route: *route,
+                        req: Box::new((inner, tx)),
+                    },
+                    tx: (),
+                    stream_id,
+                })
+                .is_ok(),
+            ChannelTx::Balanced(replicas) => {
+                let (i, x) = replicas.pick(stream_id);
+                in_flight = Some(x);
+                replicas.txs[i]
+                    .send(CoreRequest::AppRequest {
+                        inner,
+                        tx,
+                        stream_id,
+                    })
+                    .is_ok()
+            }
+        };
+        if !ok {
+            anyhow::bail!("failed to send a request");
+        }
+        Ok(in_flight)
+    }
+}
+
 pub struct Server<X, Svc: crate::Service<X>> {
     service: Svc,
     rx: flume::Receiver<CoreRequest<X, Svc::Response>>,
+    hooks: Option<Hooks<Svc>>,
+    control_tx: flume::Sender<Control<Svc>>,
+    control_rx: flume::Receiver<Control<Svc>>,
 }
 impl<X, Svc: crate::Service<X> + 'static + Send> Server<X, Svc>
 where
@@ -116,52 +248,212 @@ where
     Svc::Future: Send,
     Svc::Response: Send,
 {
-    fn new(rx: flume::Receiver<CoreRequest<X, Svc::Response>>, service: Svc) -> Self {
-        Self { service, rx: rx }
+    fn new(
+        rx: flume::Receiver<CoreRequest<X, Svc::Response>>,
+        service: Svc,
+        hooks: Option<Hooks<Svc>>,
+    ) -> Self {
+        let (control_tx, control_rx) = flume::unbounded();
+        Self {
+            service,
+            rx,
+            hooks,
+            control_tx,
+            control_rx,
+        }
     }
-    pub async fn serve(mut self, executor: impl futures::task::Spawn) {
-        use futures::future::AbortHandle;
+    /// Handle to control the server while it is serving.
+    pub fn handle(&self) -> ServerHandle<Svc> {
+        ServerHandle {
+            tx: self.control_tx.clone(),
+        }
+    }
+    /// Serve the requests until all the channels are dropped or shutdown is requested.
+    /// Returns after the handlers in flight finish.
+    ///
+    /// `Lifecycle::on_start` is called before the first request
+    /// and `Lifecycle::on_stop` after the last handler finishes.
+    pub async fn serve(self, executor: impl futures::task::Spawn) {
         use futures::task::SpawnExt;
-        let mut processings: HashMap<u64, AbortHandle> = HashMap::new();
-        let mut req_stream = self.rx.into_stream();
-        while let Some(req) = req_stream.next().await {
-            match req {
-                CoreRequest::AppRequest {
-                    inner,
-                    tx,
-                    stream_id,
-                } => {
-                    if let Some(handle) = processings.get(&stream_id) {
-                        handle.abort();
-                    }
-                    processings.remove(&stream_id);
-
-                    // back-pressure
-                    // A call of `poll_fn` here is required because some tower wrapper requires to do so.
-                    crate::poll_fn(|ctx| self.service.poll_ready(ctx))
-                        .await
-                        .ok();
-
-                    let fut = self.service.call(inner);
-                    let (fut, abort_handle) = futures::future::abortable(async move {
-                        if let Ok(rep) = fut.await {
-                            tx.send(rep).ok();
-                        }
-                    });
-                    let fut = async move {
-                        fut.await.ok();
-                    };
-                    if let Err(e) = executor.spawn(fut) {
-                        abort_handle.abort();
-                    }
+        let mut service = self.service;
+        if let Some(hooks) = &self.hooks {
+            (hooks.on_start)(&service).await;
+        }
+        let handlers = Handlers::new();
+        let controls = self.control_rx.into_stream();
+        let shutdown = serve_requests(&mut service, &self.rx, controls, |fut, tx| {
+            let (fut, abort_handle) = process(fut, tx);
+            executor
+                .spawn(handlers.track(fut))
+                .ok()
+                .map(|_| abort_handle)
+        })
+        .await;
+        if shutdown {
+            executor.spawn(reject_requests(self.rx)).ok();
+        }
+        handlers.wait().await;
+        if let Some(hooks) = &self.hooks {
+            (hooks.on_stop)(&service).await;
+        }
+    }
+}
+
+enum Control<Svc> {
+    Replace(Svc),
+    /// Apply the function to the service with the value, e.g. to replace a service of a router.
+    Update(fn(&mut Svc, Box<dyn Any + Send>), Box<dyn Any + Send>),
+    Shutdown,
+}
+
+/// Tracks the handlers in flight so that the server can wait for them.
+///
+/// Each handler holds a sender so the receiver sees the end
+/// when all of them are finished or aborted.
+struct Handlers {
+    tx: flume::Sender<()>,
+    rx: flume::Receiver<()>,
+}
+impl Handlers {
+    fn new() -> Self {
+        let (tx, rx) = flume::bounded(0);
+        Self { tx, rx }
+    }
+    fn track<Fut: std::future::Future>(
+        &self,
+        fut: Fut,
+    ) -> impl std::future::Future<Output = Fut::Output> {
+        let tx = self.tx.clone();
+        async move {
+            let out = fut.await;
+            drop(tx);
+            out
+        }
+    }
+    /// Wait for all the handlers tracked to finish.
+    async fn wait(self) {
+        drop(self.tx);
+        self.rx.recv_async().await.ok();
+    }
+}
+
+/// Handle to control a [`Server`].
+pub struct ServerHandle<Svc> {
+    tx: flume::Sender<Control<Svc>>,
+}
+impl<Svc> Clone for ServerHandle<Svc> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+        }
+    }
+}
+impl<Svc> ServerHandle<Svc> {
+    /// Replace the service of the server.
+    ///
+    /// The requests in flight finish on the old service and
+    /// the requests sent after this call are processed by the new one.
+    /// The channels to the server stay valid.
+    /// Returns the service back if the server has stopped.
+    pub fn replace(&self, svc: Svc) -> Result<(), Svc> {
+        self.tx
+            .send(Control::Replace(svc))
+            .map_err(|e| match e.into_inner() {
+                Control::Replace(svc) => svc,
+                _ => unreachable!(),
+            })
+    }
+    /// Stop the server accepting requests.
+    ///
+    /// The requests sent after this call fail.
+    /// The requests in flight are not cancelled.
+    pub fn shutdown(&self) {
+        self.tx.send(Control::Shutdown).ok();
+    }
+}
+
+/// Make a task to process a request and send back the response.
+fn process<Fut, Y, E>(
+    fut: Fut,
+    tx: ReplyTx<Y>,
+) -> (impl std::future::Future<Output = ()>, AbortHandle)
+where
+    Fut: std::future::Future<Output = Result<Y, E>>,
+{
+    let (fut, abort_handle) = futures::future::abortable(async move {
+        if let Ok(rep) = fut.await {
+            tx.send(Ok(rep)).ok();
+        }
+    });
+    let fut = async move {
+        fut.await.ok();
+    };
+    (fut, abort_handle)
+}
+
+/// Drop the requests to fail them until all the channels are dropped.
+async fn reject_requests<X, T>(rx: flume::Receiver<Envelope<X, T>>) {
+    while rx.recv_async().await.is_ok() {}
+}
+
+/// Serve loop shared by the servers.
+/// `controls` replace the service or shut the server down.
+/// `spawn` spawns a task to process a request and returns the handle to abort it.
+/// Returns true if the loop ends by shutdown.
+async fn serve_requests<X: 'static, T, Svc: crate::Service<X> + 'static>(
+    service: &mut Svc,
+    rx: &flume::Receiver<Envelope<X, T>>,
+    controls: impl futures::Stream<Item = Control<Svc>> + Unpin,
+    mut spawn: impl FnMut(Svc::Future, T) -> Option<AbortHandle>,
+) -> bool {
+    let mut processings: HashMap<u64, AbortHandle> = HashMap::new();
+    let mut req_stream = rx.stream().fuse();
+    let mut controls = controls.fuse();
+    loop {
+        // Controls go first so that the requests sent after them see the effect.
+        let req = futures::select_biased! {
+            control = controls.next() => match control {
+                Some(Control::Replace(svc)) => {
+                    *service = svc;
+                    continue;
+                }
+                Some(Control::Update(f, x)) => {
+                    f(service, x);
+                    continue;
+                }
+                Some(Control::Shutdown) => return true,
+                None => continue,
+            },
+            req = req_stream.next() => match req {
+                Some(req) => req,
+                None => return false,
+            },
+        };
+        match req {
+            Envelope::AppRequest {
+                inner,
+                tx,
+                stream_id,
+            } => {
+                if let Some(handle) = processings.get(&stream_id) {
+                    handle.abort();
+                }
+                processings.remove(&stream_id);
+
+                // back-pressure
+                // A call of `poll_fn` here is required because some tower wrapper requires to do so.
+                crate::poll_fn(|ctx| service.poll_ready(ctx)).await.ok();
+
+                let fut = service.call(inner);
+                if let Some(abort_handle) = spawn(fut, tx) {
                     processings.insert(stream_id, abort_handle);
                 }
-                CoreRequest::Cancel { stream_id } => {
-                    if let Some(handle) = processings.get(&stream_id) {
-                        handle.abort();
-                    }
-                    processings.remove(&stream_id);
+            }
+            Envelope::Cancel { stream_id } => {
+                if let Some(handle) = processings.get(&stream_id) {
+                    handle.abort();
                 }
+                processings.remove(&stream_id);
             }
         }
     }
@@ -170,6 +462,7 @@ where
 #[cfg(feature = "tokio-executor")]
 #[cfg_attr(docsrs, doc(cfg(feature = "tokio-executor")))]
 /// Tokio support.
+#[derive(Clone)]
 pub struct TokioExecutor;
 
 #[cfg(feature = "tokio-executor")]
@@ -183,9 +476,21 @@ impl futures::task::Spawn for TokioExecutor {
     }
 }
 
+#[cfg(feature = "tokio-executor")]
+impl BlockingSpawn for TokioExecutor {
+    fn spawn_blocking_obj(
+        &self,
+        f: Box<dyn FnOnce() + Send>,
+    ) -> Result<(), futures::task::SpawnError> {
+        tokio::task::spawn_blocking(f);
+        Ok(())
+    }
+}
+
 #[cfg(feature = "async-std-executor")]
 #[cfg_attr(docsrs, doc(cfg(feature = "async-std-executor")))]
 /// async-std support.
+#[derive(Clone)]
 pub struct AsyncStdExecutor;
 
 #[cfg(feature = "async-std-executor")]
@@ -198,3 +503,14 @@ impl futures::task::Spawn for AsyncStdExecutor {
         Ok(())
     }
 }
+
+#[cfg(feature = "async-std-executor")]
+impl BlockingSpawn for AsyncStdExecutor {
+    fn spawn_blocking_obj(
+        &self,
+        f: Box<dyn FnOnce() + Send>,
+    ) -> Result<(), futures::task::SpawnError> {
+        async_std::task::spawn_blocking(f);
+        Ok(())
+    }
+}
@@ -0,0 +1,147 @@
+use super::{Channel, ChannelTx, CoreRequest, Server, ServerBuilder};
+use crate::rng::Rng;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+impl<X, Svc: crate::Service<X> + 'static + Send> ServerBuilder<X, Svc>
+where
+    X: 'static + Send,
+    Svc::Future: Send,
+    Svc::Response: Send,
+{
+    /// Build `n` servers with the services made by `factory` behind one channel.
+    ///
+    /// Each request goes to the less busy one of two replicas chosen at random
+    /// (power of two choices) by the number of requests in flight.
+    /// While a stream (a clone of the channel) has requests in flight,
+    /// its next request goes to the same replica so that it aborts the previous one
+    /// as on a single server.
+    /// Spawn all the servers to serve the requests.
+    /// The servers don't call the `Lifecycle` hooks.
+    #[allow(clippy::type_complexity)]
+    pub fn replicas(
+        n: usize,
+        mut factory: impl FnMut() -> Svc,
+    ) -> (Channel<X, Svc::Response>, Vec<Server<X, Svc>>) {
+        assert!(n > 0, "at least one replica is needed");
+        let mut txs = vec![];
+        let mut servers = vec![];
+        for _ in 0..n {
+            let (tx, rx) = flume::unbounded();
+            txs.push(tx);
+            servers.push(Server::new(rx, factory(), None));
+        }
+        let seed = RandomState::new().build_hasher().finish();
+        let replicas = Replicas {
+            txs,
+            load: Arc::new(Load {
+                in_flight: (0..n).map(|_| AtomicUsize::new(0)).collect(),
+                streams: Mutex::new(HashMap::new()),
+            }),
+            rng: Mutex::new(Rng::new(seed)),
+        };
+        let chan = Channel::with_tx(ChannelTx::Balanced(Arc::new(replicas)));
+        (chan, servers)
+    }
+}
+
+pub(super) struct Replicas<X, Y> {
+    pub(super) txs: Vec<flume::Sender<CoreRequest<X, Y>>>,
+    load: Arc<Load>,
+    rng: Mutex<Rng>,
+}
+
+struct Load {
+    /// The number of requests in flight on each replica.
+    in_flight: Vec<AtomicUsize>,
+    streams: Mutex<HashMap<u64, Stream>>,
+}
+
+/// Requests of a stream.
+#[derive(Default)]
+struct Stream {
+    /// Replicas the stream has sent requests to.
+    used: Vec<usize>,
+    /// Replica which has the requests of the stream in flight and their number.
+    in_flight: Option<(usize, usize)>,
+}
+
+impl<X, Y> Replicas<X, Y> {
+    /// Choose a replica for a request of the stream and count the request in flight on it.
+    pub(super) fn pick(&self, stream_id: u64) -> (usize, InFlight) {
+        let mut streams = self.load.streams.lock().unwrap();
+        let stream = streams.entry(stream_id).or_default();
+        let i = match &mut stream.in_flight {
+            Some((i, n)) => {
+                *n += 1;
+                *i
+            }
+            None => {
+                let i = self.choose();
+                stream.in_flight = Some((i, 1));
+                i
+            }
+        };
+        if !stream.used.contains(&i) {
+            stream.used.push(i);
+        }
+        self.load.in_flight[i].fetch_add(1, Ordering::SeqCst);
+        let in_flight = InFlight {
+            load: self.load.clone(),
+            i,
+            stream_id,
+        };
+        (i, in_flight)
+    }
+    /// Forget the stream and return the replicas it has sent requests to.
+    pub(super) fn close(&self, stream_id: u64) -> Vec<usize> {
+        let mut streams = self.load.streams.lock().unwrap();
+        streams
+            .remove(&stream_id)
+            .map(|x| x.used)
+            .unwrap_or_default()
+    }
+    /// Power of two choices.
+    fn choose(&self) -> usize {
+        let n = self.txs.len();
+        if n == 1 {
+            0
+        } else {
+            let mut rng = self.rng.lock().unwrap();
+            let a = (rng.next_u64() % n as u64) as usize;
+            // Another one than `a`.
+            let b = (a + 1 + (rng.next_u64() % (n as u64 - 1)) as usize) % n;
+            let load = |i: usize| self.load.in_flight[i].load(Ordering::SeqCst);
+            if load(b) < load(a) {
+                b
+            } else {
+                a
+            }
+        }
+    }
+}
+
+/// Counts a request in flight on a replica until dropped.
+pub(super) struct InFlight {
+    load: Arc<Load>,
+    i: usize,
+    stream_id: u64,
+}
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.load.in_flight[self.i].fetch_sub(1, Ordering::SeqCst);
+        let mut streams = self.load.streams.lock().unwrap();
+        // The stream may be closed already.
+        if let Some(stream) = streams.get_mut(&self.stream_id) {
+            if let Some((_, n)) = &mut stream.in_flight {
+                *n -= 1;
+                if *n == 0 {
+                    stream.in_flight = None;
+                }
+            }
+        }
+    }
+}
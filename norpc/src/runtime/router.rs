@@ -0,0 +1,345 @@
+use super::{reject_requests, serve_requests, Channel, ChannelTx, Control, Envelope, ReplyTx};
+use super::{Handlers, Hooks};
+use crate::Service;
+use futures::future::BoxFuture;
+use std::any::Any;
+use std::convert::Infallible;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower_layer::{Identity, Layer};
+
+/// Request to one of the services of a router.
+pub(super) struct RoutedCall {
+    pub(super) route: usize,
+    /// `(X, ReplyTx<Y>)` of the service.
+    pub(super) req: Box<dyn Any + Send>,
+}
+/// The services send back the responses themselves.
+pub(super) type RoutedRequest = Envelope<RoutedCall, ()>;
+
+/// Service with the types erased.
+trait Route: Send {
+    fn poll_ready(&mut self, ctx: &mut Context<'_>) -> Poll<()>;
+    fn call(&mut self, req: Box<dyn Any + Send>) -> BoxFuture<'static, ()>;
+    fn replace(&mut self, svc: Box<dyn Any + Send>);
+    fn on_start(&mut self) -> BoxFuture<'_, ()>;
+    fn on_stop(&mut self) -> BoxFuture<'_, ()>;
+}
+
+type CloneFn<Svc> = fn(&Svc) -> Svc;
+
+/// Hooks of a route called on the service as given to the router, not the layered one.
+struct RouteHooks<Svc> {
+    hooks: Hooks<Svc>,
+    svc: Svc,
+    clone: CloneFn<Svc>,
+}
+
+struct Typed<X, Svc, L: Layer<Svc>> {
+    svc: L::Service,
+    layer: L,
+    hooks: Option<RouteHooks<Svc>>,
+    phantom_x: PhantomData<fn(X)>,
+}
+impl<X, Svc, L> Route for Typed<X, Svc, L>
+where
+    X: 'static + Send,
+    Svc: 'static + Send,
+    L: Layer<Svc> + Send,
+    L::Service: Service<X> + Send,
+    <L::Service as Service<X>>::Future: Send + 'static,
+    <L::Service as Service<X>>::Response: Send + 'static,
+{
+    fn poll_ready(&mut self, ctx: &mut Context<'_>) -> Poll<()> {
+        self.svc.poll_ready(ctx).map(|_| ())
+    }
+    fn call(&mut self, req: Box<dyn Any + Send>) -> BoxFuture<'static, ()> {
+        type Y<X, S> = <S as Service<X>>::Response;
+        let (inner, tx) = *req
+            .downcast::<(X, ReplyTx<Y<X, L::Service>>)>()
+            .expect("request is routed to a wrong service");
+        let fut = self.svc.call(inner);
+        Box::pin(async move {
+            if let Ok(rep) = fut.await {
+                tx.send(Ok(rep)).ok();
+            }
+        })
+    }
+    fn replace(&mut self, svc: Box<dyn Any + Send>) {
+        let svc = *svc
+            .downcast::<Svc>()
+            .expect("service is replaced by a wrong type");
+        if let Some(hooks) = &mut self.hooks {
+            hooks.svc = (hooks.clone)(&svc);
+        }
+        self.svc = self.layer.layer(svc);
+    }
+    fn on_start(&mut self) -> BoxFuture<'_, ()> {
+        match &self.hooks {
+            Some(hooks) => (hooks.hooks.on_start)(&hooks.svc),
+            None => Box::pin(async {}),
+        }
+    }
+    fn on_stop(&mut self) -> BoxFuture<'_, ()> {
+        match &self.hooks {
+            Some(hooks) => (hooks.hooks.on_stop)(&hooks.svc),
+            None => Box::pin(async {}),
+        }
+    }
+}
+
+/// The services of a router as one service.
+pub(super) struct Routes(Vec<Box<dyn Route>>);
+impl Service<RoutedCall> for Routes {
+    type Response = ();
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<(), Infallible>>;
+
+    fn poll_ready(&mut self, ctx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        // The services share the serve loop so one not ready holds back the others.
+        for route in &mut self.0 {
+            futures::ready!(route.poll_ready(ctx));
+        }
+        Poll::Ready(Ok(()))
+    }
+    fn call(&mut self, req: RoutedCall) -> Self::Future {
+        let fut = self.0[req.route].call(req.req);
+        Box::pin(async move {
+            fut.await;
+            Ok(())
+        })
+    }
+}
+
+/// Server of multiple services sharing one queue.
+///
+/// Each service added to the router gets its own typed channel
+/// but the requests are processed by one serve loop.
+/// The router is ready for the next request when all the services are,
+/// so a service which is not ready holds back the requests to the others.
+///
+/// ```ignore
+/// let mut router = Router::with_layer(TimeoutLayer::new(Duration::from_secs(1)));
+/// let hello_chan = router.add(HelloWorldService::new(HelloWorldApp));
+/// let kv_chan = router.add(KVStoreService::new(KVStoreApp::new()));
+/// tokio::spawn(router.serve(TokioExecutor));
+/// ```
+pub struct Router<L = Identity> {
+    routes: Routes,
+    layer: L,
+    // Shared by the channels of all the services so the stream ids don't collide.
+    next_id: Arc<AtomicU64>,
+    tx: flume::Sender<RoutedRequest>,
+    rx: flume::Receiver<RoutedRequest>,
+    control_tx: flume::Sender<Control<Routes>>,
+    control_rx: flume::Receiver<Control<Routes>>,
+}
+impl Router {
+    pub fn new() -> Self {
+        Self::with_layer(Identity::new())
+    }
+}
+impl Default for Router {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<L> Router<L> {
+    /// Router which wraps all the services added to it in `layer`.
+    pub fn with_layer(layer: L) -> Self {
+        let (tx, rx) = flume::unbounded();
+        let (control_tx, control_rx) = flume::unbounded();
+        Self {
+            routes: Routes(vec![]),
+            layer,
+            next_id: Arc::new(AtomicU64::new(0)),
+            tx,
+            rx,
+            control_tx,
+            control_rx,
+        }
+    }
+    /// Add a service and return the channel to it.
+    pub fn add<X, Svc>(&mut self, svc: Svc) -> Channel<X, <L::Service as Service<X>>::Response>
+    where
+        X: 'static + Send,
+        Svc: 'static + Send,
+        L: Layer<Svc> + Clone + Send + 'static,
+        L::Service: Service<X> + Send + 'static,
+        <L::Service as Service<X>>::Future: Send + 'static,
+        <L::Service as Service<X>>::Response: Send + 'static,
+    {
+        self.route(svc).build().0
+    }
+    /// Builder to add a service with options.
+    pub fn route<X, Svc>(&mut self, svc: Svc) -> RouteBuilder<'_, X, Svc, L> {
+        RouteBuilder {
+            router: self,
+            svc,
+            hooks: None,
+            phantom_x: PhantomData,
+        }
+    }
+    /// Handle to control the router while it is serving.
+    pub fn handle(&self) -> RouterHandle {
+        RouterHandle {
+            tx: self.control_tx.clone(),
+        }
+    }
+    /// Serve the requests to all the services until all the channels are dropped
+    /// or shutdown is requested. Returns after the handlers in flight finish.
+    ///
+    /// `Lifecycle::on_start` of the services is called in the order they are added
+    /// before the first request and `Lifecycle::on_stop` in the reverse order
+    /// after the last handler finishes.
+    pub async fn serve(self, executor: impl futures::task::Spawn) {
+        use futures::task::SpawnExt;
+        let Router {
+            mut routes,
+            tx,
+            rx,
+            control_rx,
+            ..
+        } = self;
+        // Otherwise the queue never closes.
+        drop(tx);
+
+        for route in &mut routes.0 {
+            route.on_start().await;
+        }
+        let handlers = Handlers::new();
+        let controls = control_rx.into_stream();
+        let shutdown = serve_requests(&mut routes, &rx, controls, |fut, ()| {
+            let (fut, abort_handle) = futures::future::abortable(handlers.track(fut));
+            let fut = async move {
+                fut.await.ok();
+            };
+            executor.spawn(fut).ok().map(|_| abort_handle)
+        })
+        .await;
+        if shutdown {
+            executor.spawn(reject_requests(rx)).ok();
+        }
+        handlers.wait().await;
+        for route in routes.0.iter_mut().rev() {
+            route.on_stop().await;
+        }
+    }
+}
+
+/// Builder of a service of a [`Router`].
+pub struct RouteBuilder<'a, X, Svc, L> {
+    router: &'a mut Router<L>,
+    svc: Svc,
+    hooks: Option<(Hooks<Svc>, CloneFn<Svc>)>,
+    phantom_x: PhantomData<fn(X)>,
+}
+impl<'a, X, Svc, L> RouteBuilder<'a, X, Svc, L>
+where
+    X: 'static + Send,
+    Svc: 'static + Send,
+    L: Layer<Svc> + Clone + Send + 'static,
+    L::Service: Service<X> + Send + 'static,
+    <L::Service as Service<X>>::Future: Send + 'static,
+    <L::Service as Service<X>>::Response: Send + 'static,
+{
+    /// Call the `Lifecycle` hooks of the service when the router starts and stops.
+    ///
+    /// The router keeps a clone of the service for the hooks
+    /// because the one serving the requests is wrapped in the layer.
+    pub fn lifecycle(mut self) -> Self
+    where
+        Svc: crate::Lifecycle + Clone + Sync,
+    {
+        self.hooks = Some((Hooks::new(), Svc::clone));
+        self
+    }
+    /// Add the service and return the channel and the handle to it.
+    #[allow(clippy::type_complexity)]
+    pub fn build(
+        self,
+    ) -> (
+        Channel<X, <L::Service as Service<X>>::Response>,
+        RouteHandle<Svc>,
+    ) {
+        let RouteBuilder {
+            router, svc, hooks, ..
+        } = self;
+        let route = router.routes.0.len();
+        let hooks = hooks.map(|(hooks, clone)| RouteHooks {
+            hooks,
+            svc: clone(&svc),
+            clone,
+        });
+        router.routes.0.push(Box::new(Typed::<X, Svc, L> {
+            svc: router.layer.layer(svc),
+            layer: router.layer.clone(),
+            hooks,
+            phantom_x: PhantomData,
+        }));
+        let chan = Channel {
+            stream_id: router.next_id.fetch_add(1, Ordering::SeqCst),
+            next_id: router.next_id.clone(),
+            tx: ChannelTx::Routed {
+                route,
+                tx: router.tx.clone(),
+            },
+        };
+        let handle = RouteHandle {
+            route,
+            tx: router.control_tx.clone(),
+            phantom_svc: PhantomData,
+        };
+        (chan, handle)
+    }
+}
+
+/// Handle to control a [`Router`].
+#[derive(Clone)]
+pub struct RouterHandle {
+    tx: flume::Sender<Control<Routes>>,
+}
+impl RouterHandle {
+    /// Stop the router accepting requests to any of the services.
+    ///
+    /// The requests sent after this call fail.
+    /// The requests in flight are not cancelled.
+    pub fn shutdown(&self) {
+        self.tx.send(Control::Shutdown).ok();
+    }
+}
+
+/// Handle to control a service of a [`Router`].
+pub struct RouteHandle<Svc> {
+    route: usize,
+    tx: flume::Sender<Control<Routes>>,
+    phantom_svc: PhantomData<fn(Svc)>,
+}
+impl<Svc> Clone for RouteHandle<Svc> {
+    fn clone(&self) -> Self {
+        Self {
+            route: self.route,
+            tx: self.tx.clone(),
+            phantom_svc: PhantomData,
+        }
+    }
+}
+impl<Svc: 'static + Send> RouteHandle<Svc> {
+    /// Replace the service as [`ServerHandle::replace`](super::ServerHandle::replace) does.
+    /// The new service is wrapped in the layer of the router too.
+    /// Returns the service back if the router has stopped.
+    pub fn replace(&self, svc: Svc) -> Result<(), Svc> {
+        fn replace<Svc: 'static + Send>(routes: &mut Routes, x: Box<dyn Any + Send>) {
+            let (route, svc) = *x.downcast::<(usize, Svc)>().unwrap();
+            routes.0[route].replace(Box::new(svc));
+        }
+        self.tx
+            .send(Control::Update(replace::<Svc>, Box::new((self.route, svc))))
+            .map_err(|e| match e.into_inner() {
+                Control::Update(_, x) => x.downcast::<(usize, Svc)>().unwrap().1,
+                _ => unreachable!(),
+            })
+    }
+}
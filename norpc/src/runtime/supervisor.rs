@@ -0,0 +1,337 @@
+use super::{reject_requests, serve_requests, Channel, Control, CoreRequest, ReplyTx};
+use super::{Handlers, Hooks, ServerHandle};
+use futures::future::{AbortHandle, BoxFuture};
+use futures::{FutureExt, StreamExt};
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::marker::PhantomData;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// Error for the calls pending when the service is restarted.
+#[derive(Debug)]
+pub struct Restarted;
+impl std::fmt::Display for Restarted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the service is restarted")
+    }
+}
+impl std::error::Error for Restarted {}
+
+/// How a supervisor restarts the service.
+#[derive(Clone, Debug)]
+pub enum RestartStrategy {
+    /// Restart the crashed service alone.
+    /// The supervisor gives up and fails all the following calls
+    /// if the service crashes more than `max_restarts` times within `within`.
+    OneForOne {
+        max_restarts: usize,
+        within: Duration,
+    },
+}
+impl Default for RestartStrategy {
+    fn default() -> Self {
+        RestartStrategy::OneForOne {
+            max_restarts: 3,
+            within: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Server which restarts the service when a handler panics.
+///
+/// The service is made by the factory and made again on every restart.
+/// The channels stay valid across restarts and
+/// the calls pending at a restart fail with [`Restarted`].
+/// The server runs the same serve loop as [`Server`](super::Server)
+/// so it can be controlled by a [`ServerHandle`] and waits for the handlers in flight when it stops.
+pub struct Supervisor<X, Svc> {
+    factory: Box<dyn FnMut() -> Svc + Send>,
+    strategy: RestartStrategy,
+    hooks: Option<Hooks<Svc>>,
+    phantom_x: PhantomData<X>,
+}
+impl<X, Svc: crate::Service<X> + 'static + Send> Supervisor<X, Svc>
+where
+    X: 'static + Send,
+    Svc::Future: Send,
+    Svc::Response: Send,
+{
+    pub fn new(factory: impl FnMut() -> Svc + Send + 'static) -> Self {
+        Self {
+            factory: Box::new(factory),
+            strategy: RestartStrategy::default(),
+            hooks: None,
+            phantom_x: PhantomData,
+        }
+    }
+    pub fn strategy(mut self, strategy: RestartStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+    /// Call the `Lifecycle` hooks of the service when the server starts and stops.
+    ///
+    /// `on_start` is called on the first service only and
+    /// `on_stop` on the service the server has when it stops.
+    /// Do the setup of the restarted services in the factory.
+    pub fn lifecycle(mut self) -> Self
+    where
+        Svc: crate::Lifecycle + Sync,
+    {
+        self.hooks = Some(Hooks::new());
+        self
+    }
+    pub fn build(self) -> (Channel<X, Svc::Response>, SupervisedServer<X, Svc>) {
+        let (tx, rx) = flume::unbounded();
+        let (control_tx, control_rx) = flume::unbounded();
+        let server = SupervisedServer {
+            factory: self.factory,
+            strategy: self.strategy,
+            hooks: self.hooks,
+            rx,
+            control_tx,
+            control_rx,
+        };
+        let chan = Channel::new(tx);
+        (chan, server)
+    }
+}
+
+/// Service which turns a panic in `call` into a panic of the handler
+/// so that the supervisor sees it as a crash.
+struct CatchCall<S>(S);
+impl<X, S: crate::Service<X>> crate::Service<X> for CatchCall<S>
+where
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<S::Response, S::Error>>;
+
+    fn poll_ready(&mut self, ctx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.0.poll_ready(ctx)
+    }
+    fn call(&mut self, req: X) -> Self::Future {
+        match std::panic::catch_unwind(AssertUnwindSafe(|| self.0.call(req))) {
+            Ok(fut) => Box::pin(fut),
+            Err(panic) => Box::pin(futures::future::lazy(
+                move |_| -> Result<S::Response, S::Error> { std::panic::resume_unwind(panic) },
+            )),
+        }
+    }
+}
+
+/// Reply sender shared by the handler and the supervisor.
+type SharedReplyTx<Y> = Arc<Mutex<Option<ReplyTx<Y>>>>;
+
+/// State shared by the serve loop and the restarts.
+struct State<Y> {
+    /// Incremented when the service is restarted or replaced.
+    generation: u64,
+    next_id: u64,
+    handlers: HashMap<u64, (AbortHandle, SharedReplyTx<Y>)>,
+}
+impl<Y> State<Y> {
+    /// Abort all the handlers and fail their calls.
+    fn fail_all(&mut self) {
+        for (_, (handle, tx)) in self.handlers.drain() {
+            handle.abort();
+            if let Some(tx) = tx.lock().unwrap().take() {
+                tx.send(Err(Restarted)).ok();
+            }
+        }
+    }
+}
+
+/// Panic of a handler reported to the supervisor.
+struct Panic {
+    generation: u64,
+    id: u64,
+}
+
+/// Make a task to process a request which reports a panic to the supervisor.
+/// The task resolves to true if the handler panicked.
+fn supervise<Fut, Y, E>(
+    fut: Fut,
+    tx: SharedReplyTx<Y>,
+    panic_tx: flume::Sender<Panic>,
+    panic: Panic,
+) -> (impl std::future::Future<Output = bool>, AbortHandle)
+where
+    Fut: std::future::Future<Output = Result<Y, E>>,
+{
+    let (fut, abort_handle) = futures::future::abortable(async move {
+        match AssertUnwindSafe(fut).catch_unwind().await {
+            Ok(Ok(rep)) => {
+                if let Some(tx) = tx.lock().unwrap().take() {
+                    tx.send(Ok(rep)).ok();
+                }
+                false
+            }
+            Ok(Err(_)) => false,
+            Err(_) => {
+                panic_tx.send(panic).ok();
+                true
+            }
+        }
+    });
+    let fut = async move { fut.await.unwrap_or(false) };
+    (fut, abort_handle)
+}
+
+pub struct SupervisedServer<X, Svc: crate::Service<X>> {
+    factory: Box<dyn FnMut() -> Svc + Send>,
+    strategy: RestartStrategy,
+    hooks: Option<Hooks<Svc>>,
+    rx: flume::Receiver<CoreRequest<X, Svc::Response>>,
+    control_tx: flume::Sender<Control<Svc>>,
+    control_rx: flume::Receiver<Control<Svc>>,
+}
+impl<X, Svc: crate::Service<X> + 'static + Send> SupervisedServer<X, Svc>
+where
+    X: 'static + Send,
+    Svc::Future: Send,
+    Svc::Response: Send,
+{
+    /// Handle to control the server while it is serving.
+    ///
+    /// A service given by [`ServerHandle::replace`] is restarted by the factory when it crashes.
+    pub fn handle(&self) -> ServerHandle<Svc> {
+        ServerHandle {
+            tx: self.control_tx.clone(),
+        }
+    }
+    /// Serve the requests until all the channels are dropped, shutdown is requested
+    /// or the supervisor gives up restarting the service.
+    /// Returns after the handlers in flight finish.
+    pub async fn serve(self, executor: impl futures::task::Spawn) {
+        use futures::task::SpawnExt;
+        let SupervisedServer {
+            mut factory,
+            strategy,
+            hooks,
+            rx,
+            control_rx,
+            ..
+        } = self;
+
+        let mut service = CatchCall(factory());
+        if let Some(hooks) = &hooks {
+            (hooks.on_start)(&service.0).await;
+        }
+        let state = Arc::new(Mutex::new(State {
+            generation: 0,
+            next_id: 0,
+            handlers: HashMap::new(),
+        }));
+
+        let controls = {
+            let state = state.clone();
+            control_rx.into_stream().map(move |control| match control {
+                Control::Replace(svc) => {
+                    // The panics of the handlers on the old service are ignored.
+                    state.lock().unwrap().generation += 1;
+                    Control::Replace(CatchCall(svc))
+                }
+                Control::Update(f, x) => Control::Update(update::<Svc>, Box::new((f, x))),
+                Control::Shutdown => Control::Shutdown,
+            })
+        };
+        // Handlers report a panic with the generation of the service they ran on.
+        let (panic_tx, panic_rx) = flume::unbounded::<Panic>();
+        let restarts = {
+            let state = state.clone();
+            let mut restarts = VecDeque::new();
+            panic_rx.into_stream().filter_map(move |panic| {
+                let mut state = state.lock().unwrap();
+                let control = if panic.generation != state.generation {
+                    // Fail the call of the handler alone.
+                    state.handlers.remove(&panic.id);
+                    None
+                } else {
+                    state.generation += 1;
+                    state.fail_all();
+                    if give_up(&strategy, &mut restarts) {
+                        Some(Control::Shutdown)
+                    } else {
+                        Some(Control::Replace(CatchCall(factory())))
+                    }
+                };
+                futures::future::ready(control)
+            })
+        };
+        let controls = Box::pin(futures::stream::select(controls, restarts));
+
+        let handlers = Handlers::new();
+        let shutdown = serve_requests(&mut service, &rx, controls, |fut, tx| {
+            let tx = Arc::new(Mutex::new(Some(tx)));
+            let mut st = state.lock().unwrap();
+            st.next_id += 1;
+            let panic = Panic {
+                generation: st.generation,
+                id: st.next_id,
+            };
+            let id = panic.id;
+            let (fut, abort_handle) = supervise(fut, tx.clone(), panic_tx.clone(), panic);
+            st.handlers.insert(id, (abort_handle.clone(), tx));
+            drop(st);
+            let fut = {
+                let state = state.clone();
+                async move {
+                    // The supervisor fails the call of a panicked handler.
+                    if !fut.await {
+                        state.lock().unwrap().handlers.remove(&id);
+                    }
+                }
+            };
+            match executor.spawn(handlers.track(fut)) {
+                Ok(()) => Some(abort_handle),
+                Err(_) => {
+                    state.lock().unwrap().handlers.remove(&id);
+                    None
+                }
+            }
+        })
+        .await;
+        if shutdown {
+            executor.spawn(reject_requests(rx)).ok();
+        }
+        handlers.wait().await;
+        if let Some(hooks) = &hooks {
+            (hooks.on_stop)(&service.0).await;
+        }
+    }
+}
+
+/// Apply an update to the service inside the wrapper.
+fn update<Svc: 'static>(svc: &mut CatchCall<Svc>, x: Box<dyn Any + Send>) {
+    type Update<Svc> = (fn(&mut Svc, Box<dyn Any + Send>), Box<dyn Any + Send>);
+    let (f, x) = *x.downcast::<Update<Svc>>().unwrap();
+    f(&mut svc.0, x);
+}
+
+/// Count a restart and return true if the strategy gives up.
+fn give_up(strategy: &RestartStrategy, restarts: &mut VecDeque<Instant>) -> bool {
+    match *strategy {
+        RestartStrategy::OneForOne {
+            max_restarts,
+            within,
+        } => {
+            let now = Instant::now();
+            while let Some(t) = restarts.front() {
+                if now.duration_since(*t) > within {
+                    restarts.pop_front();
+                } else {
+                    break;
+                }
+            }
+            if restarts.len() >= max_restarts {
+                return true;
+            }
+            restarts.push_back(now);
+            false
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! The generated `{Svc}ShardedClient` routes a request of a method
+//! with a `#[shard_key]` argument to the partition the key hashes to.
+//!
+//! ```ignore
+//! #[norpc::service]
+//! trait KVStore {
+//!     fn read(#[shard_key] id: u64) -> Option<String>;
+//!     fn write(#[shard_key] id: u64, s: String);
+//! }
+//! let cli = KVStoreShardedClient::new(chans);
+//! ```
+//!
+//! The methods marked `#[broadcast]` are called on all the partitions
+//! by the generated `broadcast_<method>`. They needn't be in a service with shard keys.
+//!
+//! ```ignore
+//! #[norpc::service]
+//! trait Index {
+//!     #[broadcast]
+//!     fn search(query: String) -> Vec<u64>;
+//! }
+//! let rep = IndexShardedClient::new(chans)
+//!     .broadcast_search("norpc".to_owned(), Duration::from_secs(1))
+//!     .await;
+//! ```
+
+use futures::future::{self, Either, FutureExt};
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+/// Partition out of `n` the key belongs to.
+///
+/// The hash doesn't depend on the process (unlike `DefaultHasher`)
+/// but it does on the `Hash` impl of the key, which may change between
+/// Rust versions or differ between targets (e.g. for `usize`).
+/// So a key goes to the same partition among the processes of the same build on the same target.
+/// Don't persist the partitions beyond that.
+///
+/// # Panics
+///
+/// Panics if `n` is 0.
+pub fn partition<K: Hash + ?Sized>(key: &K, n: usize) -> usize {
+    assert!(n > 0, "the number of partitions must be positive");
+    let mut h = Fnv::default();
+    key.hash(&mut h);
+    (h.finish() % n as u64) as usize
+}
+
+/// FNV-1a
+struct Fnv(u64);
+impl Default for Fnv {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+impl Hasher for Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Error of a call to one of the partitions in a broadcast.
+#[derive(Debug)]
+pub enum BroadcastError<E> {
+    /// The partition didn't respond by the deadline.
+    Timeout,
+    /// The partition failed.
+    Service(E),
+}
+impl<E: std::fmt::Display> std::fmt::Display for BroadcastError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BroadcastError::Timeout => write!(f, "the partition didn't respond in time"),
+            BroadcastError::Service(e) => write!(f, "the partition failed: {}", e),
+        }
+    }
+}
+impl<E: std::fmt::Debug + std::fmt::Display> std::error::Error for BroadcastError<E> {}
+
+/// Run the calls concurrently and collect their results in order.
+/// The calls not finished in `timeout` result in `BroadcastError::Timeout`
+/// and are dropped then.
+#[doc(hidden)]
+pub async fn gather<T, E, Fut>(
+    calls: Vec<Fut>,
+    timeout: Duration,
+) -> Vec<Result<T, BroadcastError<E>>>
+where
+    Fut: Future<Output = Result<T, E>>,
+{
+    let deadline = futures_timer::Delay::new(timeout).shared();
+    let calls = calls.into_iter().map(|call| {
+        let deadline = deadline.clone();
+        async move {
+            futures::pin_mut!(call);
+            match future::select(call, deadline).await {
+                Either::Left((rep, _)) => rep.map_err(BroadcastError::Service),
+                Either::Right(_) => Err(BroadcastError::Timeout),
+            }
+        }
+    });
+    future::join_all(calls).await
+}
@@ -0,0 +1,256 @@
+use crate::rng::Rng;
+use futures::future::BoxFuture;
+use futures::task::{waker, ArcWake, FutureObj, Spawn, SpawnError};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Id of the future passed to `block_on`.
+const MAIN_TASK: u64 = 0;
+
+struct Shared {
+    tasks: Mutex<HashMap<u64, BoxFuture<'static, ()>>>,
+    ready: Mutex<BTreeSet<u64>>,
+    next_id: Mutex<u64>,
+    now: Mutex<Duration>,
+    timers: Mutex<BTreeMap<(Duration, u64), std::task::Waker>>,
+}
+impl Shared {
+    fn next_id(&self) -> u64 {
+        let mut next_id = self.next_id.lock().unwrap();
+        *next_id += 1;
+        *next_id
+    }
+}
+
+struct TaskWaker {
+    id: u64,
+    shared: Arc<Shared>,
+}
+impl ArcWake for TaskWaker {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.shared.ready.lock().unwrap().insert(arc_self.id);
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<Shared>>> = const { RefCell::new(None) };
+}
+fn current() -> Arc<Shared> {
+    CURRENT.with(|x| x.borrow().clone().expect("must be called in Sim::block_on"))
+}
+
+/// Deterministic executor for simulation.
+///
+/// All the futures run on the current thread and the next future to poll is
+/// chosen randomly among the ready ones by a generator seeded with the given seed.
+/// So races between servers and clients happen in a random but reproducible order
+/// and a failing run can be replayed exactly from its seed.
+///
+/// Time is virtual. When no future is ready, the clock jumps to the next timer
+/// registered by [`sleep`]. Futures must not wait for anything outside of the simulation.
+///
+/// ```ignore
+/// let sim = Sim::new(seed);
+/// sim.block_on(async {
+///     let (chan, server) = ServerBuilder::new(svc).build();
+///     sim::spawn(server.serve(sim::SimExecutor::current()));
+///     let mut cli = HelloWorldClient::new(chan);
+///     cli.hello("World".to_owned()).await
+/// });
+/// ```
+pub struct Sim {
+    shared: Arc<Shared>,
+    rng: RefCell<Rng>,
+}
+impl Sim {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                tasks: Mutex::new(HashMap::new()),
+                ready: Mutex::new(BTreeSet::new()),
+                next_id: Mutex::new(MAIN_TASK),
+                now: Mutex::new(Duration::ZERO),
+                timers: Mutex::new(BTreeMap::new()),
+            }),
+            rng: RefCell::new(Rng::new(seed)),
+        }
+    }
+    /// Executor to spawn futures into this simulation.
+    pub fn executor(&self) -> SimExecutor {
+        SimExecutor {
+            shared: self.shared.clone(),
+        }
+    }
+    /// Virtual time elapsed since the simulation started.
+    pub fn now(&self) -> Duration {
+        *self.shared.now.lock().unwrap()
+    }
+    /// Run the simulation until `fut` completes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fut` can't complete because no future is ready and no timer is pending.
+    pub fn block_on<F: Future>(&self, fut: F) -> F::Output {
+        let prev = CURRENT.with(|x| x.borrow_mut().replace(self.shared.clone()));
+        struct Restore(Option<Arc<Shared>>);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                CURRENT.with(|x| *x.borrow_mut() = self.0.take());
+            }
+        }
+        let _restore = Restore(prev);
+
+        let mut fut = Box::pin(fut);
+        let main_waker = waker(Arc::new(TaskWaker {
+            id: MAIN_TASK,
+            shared: self.shared.clone(),
+        }));
+        self.shared.ready.lock().unwrap().insert(MAIN_TASK);
+        loop {
+            let id = match self.pick() {
+                Some(id) => id,
+                None => {
+                    self.advance_clock();
+                    continue;
+                }
+            };
+            if id == MAIN_TASK {
+                let mut ctx = Context::from_waker(&main_waker);
+                if let Poll::Ready(x) = fut.as_mut().poll(&mut ctx) {
+                    return x;
+                }
+            } else {
+                let task = self.shared.tasks.lock().unwrap().remove(&id);
+                if let Some(mut task) = task {
+                    let task_waker = waker(Arc::new(TaskWaker {
+                        id,
+                        shared: self.shared.clone(),
+                    }));
+                    let mut ctx = Context::from_waker(&task_waker);
+                    if task.as_mut().poll(&mut ctx).is_pending() {
+                        self.shared.tasks.lock().unwrap().insert(id, task);
+                    }
+                }
+            }
+        }
+    }
+    /// Choose the next task to poll among the ready ones.
+    fn pick(&self) -> Option<u64> {
+        let mut ready = self.shared.ready.lock().unwrap();
+        if ready.is_empty() {
+            return None;
+        }
+        let i = (self.rng.borrow_mut().next_u64() % ready.len() as u64) as usize;
+        let id = *ready.iter().nth(i).unwrap();
+        ready.remove(&id);
+        Some(id)
+    }
+    /// Move the clock forward to the next timer and fire all the timers due.
+    fn advance_clock(&self) {
+        let mut timers = self.shared.timers.lock().unwrap();
+        let next = match timers.keys().next() {
+            Some((t, _)) => *t,
+            None => panic!("deadlock: no future is ready and no timer is pending"),
+        };
+        let mut now = self.shared.now.lock().unwrap();
+        if next > *now {
+            *now = next;
+        }
+        while let Some(entry) = timers.first_entry() {
+            if entry.key().0 > *now {
+                break;
+            }
+            entry.remove().wake();
+        }
+    }
+}
+
+/// Executor to spawn futures into a simulation.
+#[derive(Clone)]
+pub struct SimExecutor {
+    shared: Arc<Shared>,
+}
+impl SimExecutor {
+    /// Executor of the simulation running on this thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if not called in `Sim::block_on`.
+    pub fn current() -> Self {
+        Self { shared: current() }
+    }
+}
+impl Spawn for SimExecutor {
+    fn spawn_obj(&self, future: FutureObj<'static, ()>) -> Result<(), SpawnError> {
+        let id = self.shared.next_id();
+        self.shared
+            .tasks
+            .lock()
+            .unwrap()
+            .insert(id, Box::pin(future));
+        self.shared.ready.lock().unwrap().insert(id);
+        Ok(())
+    }
+}
+
+/// Spawn a future into the simulation running on this thread.
+pub fn spawn(fut: impl Future<Output = ()> + Send + 'static) {
+    use futures::task::SpawnExt;
+    SimExecutor::current().spawn(fut).ok();
+}
+
+/// Virtual time elapsed since the simulation running on this thread started.
+pub fn now() -> Duration {
+    *current().now.lock().unwrap()
+}
+
+/// Wait until `dur` passes in virtual time.
+pub fn sleep(dur: Duration) -> Sleep {
+    let shared = current();
+    let deadline = *shared.now.lock().unwrap() + dur;
+    Sleep {
+        shared,
+        deadline,
+        timer: None,
+    }
+}
+
+/// Future returned by [`sleep`].
+pub struct Sleep {
+    shared: Arc<Shared>,
+    deadline: Duration,
+    /// Key of the timer once registered.
+    timer: Option<(Duration, u64)>,
+}
+impl Future for Sleep {
+    type Output = ();
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<()> {
+        if *self.shared.now.lock().unwrap() >= self.deadline {
+            return Poll::Ready(());
+        }
+        let deadline = self.deadline;
+        let shared = self.shared.clone();
+        let timer = *self
+            .timer
+            .get_or_insert_with(|| (deadline, shared.next_id()));
+        // Registers the timer on the first poll and updates the waker on the later ones.
+        shared
+            .timers
+            .lock()
+            .unwrap()
+            .insert(timer, ctx.waker().clone());
+        Poll::Pending
+    }
+}
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(timer) = self.timer {
+            self.shared.timers.lock().unwrap().remove(&timer);
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use crate::rng::Rng;
+use crate::Schema;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Faults to inject into the calls of a method.
+/// Each fault happens with the given probability.
+#[derive(Clone, Default)]
+pub struct Faults {
+    latency: Option<(f64, Duration)>,
+    error: f64,
+    drop: f64,
+    panic: f64,
+}
+impl Faults {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Delay the call by `delay`.
+    pub fn latency(mut self, p: f64, delay: Duration) -> Self {
+        self.latency = Some((p, delay));
+        self
+    }
+    /// Fail the call without calling the inner service.
+    pub fn error(mut self, p: f64) -> Self {
+        self.error = p;
+        self
+    }
+    /// Call the inner service but never return the response.
+    pub fn drop_response(mut self, p: f64) -> Self {
+        self.drop = p;
+        self
+    }
+    /// Panic in the handler.
+    pub fn panic(mut self, p: f64) -> Self {
+        self.panic = p;
+        self
+    }
+}
+
+enum Roll {
+    Panic,
+    Error,
+    Drop(Option<Duration>),
+    Latency(Duration),
+    None,
+}
+
+#[derive(Clone)]
+struct Config {
+    methods: HashMap<&'static str, Faults>,
+    default: Faults,
+    // Shared by the copies of the config so that one seed gives one sequence.
+    rng: Arc<Mutex<Rng>>,
+}
+impl Config {
+    fn roll(&self, method: &str) -> Roll {
+        let faults = self.methods.get(method).unwrap_or(&self.default);
+        let mut rng = self.rng.lock().unwrap();
+        // Always roll every fault so the sequence of the random numbers
+        // doesn't depend on the outcome.
+        let panic = rng.gen_bool(faults.panic);
+        let error = rng.gen_bool(faults.error);
+        let drop = rng.gen_bool(faults.drop);
+        let latency = match faults.latency {
+            Some((p, delay)) if rng.gen_bool(p) => Some(delay),
+            _ => None,
+        };
+        if panic {
+            Roll::Panic
+        } else if error {
+            Roll::Error
+        } else if drop {
+            Roll::Drop(latency)
+        } else if let Some(delay) = latency {
+            Roll::Latency(delay)
+        } else {
+            Roll::None
+        }
+    }
+}
+
+/// Layer to inject faults into a service for chaos testing.
+///
+/// Faults are configured per method name and
+/// decided by a random number generator seeded with the given seed,
+/// so the same sequence of calls gets the same faults.
+///
+/// ```ignore
+/// let svc = ServiceBuilder::new()
+///     .layer(
+///         FaultLayer::new(seed)
+///             .method("read", Faults::new().latency(0.5, Duration::from_millis(10)))
+///             .method("write", Faults::new().error(0.1).drop_response(0.1)),
+///     )
+///     .service(KVStoreService::new(app));
+/// let (chan, server) = ServerBuilder::new(svc).build();
+/// ```
+#[derive(Clone)]
+pub struct FaultLayer {
+    config: Arc<Config>,
+}
+impl FaultLayer {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            config: Arc::new(Config {
+                methods: HashMap::new(),
+                default: Faults::new(),
+                rng: Arc::new(Mutex::new(Rng::new(seed))),
+            }),
+        }
+    }
+    /// Set the faults for the method.
+    ///
+    /// The services already made by this layer or its clones are not affected.
+    pub fn method(mut self, name: &'static str, faults: Faults) -> Self {
+        Arc::make_mut(&mut self.config).methods.insert(name, faults);
+        self
+    }
+    /// Set the faults for the methods not configured by `method`.
+    ///
+    /// The services already made by this layer or its clones are not affected.
+    pub fn default_faults(mut self, faults: Faults) -> Self {
+        Arc::make_mut(&mut self.config).default = faults;
+        self
+    }
+}
+impl<S> tower_layer::Layer<S> for FaultLayer {
+    type Service = Fault<S>;
+    fn layer(&self, inner: S) -> Self::Service {
+        Fault {
+            inner,
+            config: self.config.clone(),
+        }
+    }
+}
+
+/// Service made by `FaultLayer`.
+#[derive(Clone)]
+pub struct Fault<S> {
+    inner: S,
+    config: Arc<Config>,
+}
+
+/// Error of a service with injected faults.
+#[derive(Debug)]
+pub enum FaultError<E> {
+    /// The error is injected.
+    Injected,
+    /// The error is from the inner service.
+    Inner(E),
+}
+impl<E: std::fmt::Debug> std::fmt::Display for FaultError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FaultError::Injected => write!(f, "injected error"),
+            FaultError::Inner(e) => write!(f, "{:?}", e),
+        }
+    }
+}
+impl<E: std::fmt::Debug> std::error::Error for FaultError<E> {}
+
+impl<X, S> crate::Service<X> for Fault<S>
+where
+    X: Schema,
+    S: crate::Service<X>,
+    S::Response: Send,
+    S::Error: Send,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = FaultError<S::Error>;
+    type Future = std::pin::Pin<
+        Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>,
+    >;
+
+    fn poll_ready(
+        &mut self,
+        ctx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(ctx).map_err(FaultError::Inner)
+    }
+
+    fn call(&mut self, req: X) -> Self::Future {
+        let method = req.method_name();
+        match self.config.roll(method) {
+            Roll::Panic => Box::pin(async move { panic!("injected panic in {}", method) }),
+            Roll::Error => Box::pin(async { Err(FaultError::Injected) }),
+            Roll::Drop(latency) => {
+                let fut = self.inner.call(req);
+                Box::pin(async move {
+                    if let Some(delay) = latency {
+                        futures_timer::Delay::new(delay).await;
+                    }
+                    fut.await.ok();
+                    futures::future::pending().await
+                })
+            }
+            Roll::Latency(delay) => {
+                let fut = self.inner.call(req);
+                Box::pin(async move {
+                    futures_timer::Delay::new(delay).await;
+                    fut.await.map_err(FaultError::Inner)
+                })
+            }
+            Roll::None => {
+                let fut = self.inner.call(req);
+                Box::pin(async move { fut.await.map_err(FaultError::Inner) })
+            }
+        }
+    }
+}
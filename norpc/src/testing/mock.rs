@@ -0,0 +1,143 @@
+use std::sync::{Arc, Mutex};
+
+type Matcher<A> = Box<dyn Fn(&A) -> bool + Send>;
+type Action<A, R> = Box<dyn FnMut(A) -> R + Send>;
+
+struct ExpectationState<A, R> {
+    matcher: Option<Matcher<A>>,
+    action: Option<Action<A, R>>,
+    times: Option<usize>,
+    calls: usize,
+}
+
+/// Expectation of calls to a method of a generated `{Svc}Mock`.
+///
+/// The arguments of a call are passed as a tuple.
+/// An expectation is set up by chaining the methods
+/// on the value returned by `expect_<method>()`.
+///
+/// ```ignore
+/// mock.expect_query()
+///     .with(|(name,)| *name == 1)
+///     .return_const(Some(10))
+///     .times(1);
+/// ```
+pub struct Expectation<A, R> {
+    state: Arc<Mutex<ExpectationState<A, R>>>,
+}
+impl<A, R> Clone for Expectation<A, R> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+        }
+    }
+}
+impl<A, R> Expectation<A, R> {
+    fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(ExpectationState {
+                matcher: None,
+                action: None,
+                times: None,
+                calls: 0,
+            })),
+        }
+    }
+    /// Only match the calls whose arguments satisfy `f`.
+    pub fn with(self, f: impl Fn(&A) -> bool + Send + 'static) -> Self {
+        self.state.lock().unwrap().matcher = Some(Box::new(f));
+        self
+    }
+    /// Compute the return value from the arguments.
+    pub fn returning(self, f: impl FnMut(A) -> R + Send + 'static) -> Self {
+        self.state.lock().unwrap().action = Some(Box::new(f));
+        self
+    }
+    /// Return a clone of `v`.
+    pub fn return_const(self, v: R) -> Self
+    where
+        R: Clone + Send + 'static,
+    {
+        self.returning(move |_| v.clone())
+    }
+    /// Expect exactly `n` calls.
+    /// Calls over `n` times fall through to the next matching expectation.
+    pub fn times(self, n: usize) -> Self {
+        self.state.lock().unwrap().times = Some(n);
+        self
+    }
+    /// Number of calls matched by this expectation so far.
+    pub fn call_count(&self) -> usize {
+        self.state.lock().unwrap().calls
+    }
+}
+
+/// Expectations of a method of a generated `{Svc}Mock`.
+#[doc(hidden)]
+pub struct Method<A, R> {
+    name: String,
+    expectations: Mutex<Vec<Expectation<A, R>>>,
+}
+impl<A, R> Method<A, R> {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            expectations: Mutex::new(vec![]),
+        }
+    }
+    pub fn expect(&self) -> Expectation<A, R> {
+        let e = Expectation::new();
+        self.expectations.lock().unwrap().push(e.clone());
+        e
+    }
+    /// Call the first expectation that matches the arguments and isn't saturated.
+    pub fn call(&self, args: A) -> R {
+        let expectations = self.expectations.lock().unwrap().clone();
+        let mut matched = false;
+        for e in expectations {
+            let mut state = e.state.lock().unwrap();
+            if let Some(matcher) = &state.matcher {
+                if !matcher(&args) {
+                    continue;
+                }
+            }
+            matched = true;
+            if let Some(n) = state.times {
+                if state.calls >= n {
+                    continue;
+                }
+            }
+            state.calls += 1;
+            match &mut state.action {
+                Some(f) => return f(args),
+                None => panic!("no return value is set for {}", self.name),
+            }
+        }
+        if matched {
+            panic!("{} is called more times than expected", self.name)
+        } else {
+            panic!("unexpected call to {}", self.name)
+        }
+    }
+    /// Panic if an expectation with `times` is not satisfied.
+    pub fn checkpoint(&self) {
+        for e in self.expectations.lock().unwrap().iter() {
+            let state = e.state.lock().unwrap();
+            if let Some(n) = state.times {
+                if state.calls != n {
+                    panic!(
+                        "{} is expected to be called {} times but called {} times",
+                        self.name, n, state.calls
+                    );
+                }
+            }
+        }
+    }
+}
+impl<A, R> Drop for Method<A, R> {
+    fn drop(&mut self) {
+        if !std::thread::panicking() {
+            self.checkpoint();
+        }
+    }
+}
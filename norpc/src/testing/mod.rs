@@ -0,0 +1,9 @@
+mod fault;
+mod mock;
+#[cfg(feature = "test-spawn")]
+mod spawn;
+pub use fault::*;
+pub use mock::*;
+#[cfg(feature = "test-spawn")]
+#[cfg_attr(docsrs, doc(cfg(feature = "test-spawn")))]
+pub use spawn::*;
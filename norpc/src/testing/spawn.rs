@@ -0,0 +1,111 @@
+use crate::runtime::{Channel, ServerBuilder, TokioExecutor};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// Spawn a server of `svc` on a thread of its own with a current-thread tokio runtime.
+///
+/// Returns the channel to the server and a guard which shuts the server down,
+/// waits for the handlers in flight and asserts that none of them panicked when dropped.
+/// Because the server has its own thread, the guard can wait for it
+/// without blocking the runtime of the test.
+///
+/// ```ignore
+/// let (chan, _guard) = norpc::testing::spawn(HelloWorldService::new(app));
+/// let mut cli = HelloWorldClient::new(chan);
+/// ```
+pub fn spawn<X, Svc>(svc: Svc) -> (Channel<X, Svc::Response>, TestGuard)
+where
+    X: 'static + Send,
+    Svc: crate::Service<X> + 'static + Send,
+    Svc::Future: Send,
+    Svc::Response: Send,
+    Svc::Error: Send,
+{
+    let panicked = Arc::new(AtomicBool::new(false));
+    let svc = CatchPanic {
+        inner: svc,
+        panicked: panicked.clone(),
+    };
+    let (chan, server) = ServerBuilder::new(svc).build();
+    let handle = server.handle();
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("failed to build a runtime for the server");
+    let thread = std::thread::Builder::new()
+        .name("norpc-test-server".to_owned())
+        .spawn(move || rt.block_on(server.serve(TokioExecutor)))
+        .expect("failed to spawn the server thread");
+    let guard = TestGuard {
+        shutdown: Box::new(move || handle.shutdown()),
+        thread: Some(thread),
+        panicked,
+    };
+    (chan, guard)
+}
+
+/// Guard of the server spawned by [`spawn`].
+pub struct TestGuard {
+    shutdown: Box<dyn Fn() + Send>,
+    thread: Option<std::thread::JoinHandle<()>>,
+    panicked: Arc<AtomicBool>,
+}
+impl TestGuard {
+    /// Whether any handler has panicked so far.
+    pub fn panicked(&self) -> bool {
+        self.panicked.load(Ordering::SeqCst)
+    }
+}
+impl Drop for TestGuard {
+    fn drop(&mut self) {
+        (self.shutdown)();
+        // Don't wait for the handlers if the test has failed already.
+        if std::thread::panicking() {
+            return;
+        }
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+        if self.panicked() {
+            panic!("a handler panicked in the server");
+        }
+    }
+}
+
+/// Service which flags the panics in the handlers.
+struct CatchPanic<S> {
+    inner: S,
+    panicked: Arc<AtomicBool>,
+}
+impl<X, S: crate::Service<X>> crate::Service<X> for CatchPanic<S>
+where
+    S::Future: Send + 'static,
+    S::Response: Send,
+    S::Error: Send,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<S::Response, S::Error>>;
+
+    fn poll_ready(&mut self, ctx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(ctx)
+    }
+
+    fn call(&mut self, req: X) -> Self::Future {
+        let fut = self.inner.call(req);
+        let panicked = self.panicked.clone();
+        Box::pin(async move {
+            match AssertUnwindSafe(fut).catch_unwind().await {
+                Ok(rep) => rep,
+                Err(e) => {
+                    panicked.store(true, Ordering::SeqCst);
+                    std::panic::resume_unwind(e)
+                }
+            }
+        })
+    }
+}